@@ -1,8 +1,21 @@
-use crate::session::SessionList;
+use crate::daemon::{self, Request};
+use crate::duration::{format_duration, parse_duration};
+use crate::export::{self, Format};
+use crate::log::{Changes, Log};
+use crate::render::{self, Output};
+use crate::session::{parse_tags, Session, SessionList, SessionStatus};
 use crate::settings::Settings;
-use crate::timers::Timer;
-use crate::ui;
+use crate::state::CurrentState;
+use crate::stats::{Breakdown, Heatmap, Histograms, Period, Summary};
+use crate::status::{self, Status, StatusView};
+use crate::timers::{self, Timer};
+use crate::ui::{self, History};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Utc};
 use clap::{Parser, Subcommand};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(
@@ -14,56 +27,616 @@ use clap::{Parser, Subcommand};
 struct Opts {
     #[command(subcommand)]
     command: Option<Command>, // Allow running with no subcommand
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t,
+        help = "How stats, history and status are printed"
+    )]
+    output: Output,
 }
 
 #[derive(Subcommand)]
 enum Command {
-    /// Runs a Pomodoro session with configurable work/break times.
+    /// Runs Pomodoro sessions with configurable work/break times.
     Run {
-        #[arg(long, help = "Duration of work")]
-        work: Option<u64>,
+        #[arg(
+            long,
+            value_parser = parse_duration,
+            help = "Duration of work, e.g. 25m, 90s, 1h30m or 1:30:00. A bare number is minutes"
+        )]
+        work: Option<Duration>,
 
         // `break_` because `break` is a reserved keyword
-        #[arg(long, help = "Duration of break")]
-        break_: Option<u64>,
+        #[arg(
+            long,
+            value_parser = parse_duration,
+            help = "Duration of break, e.g. 5m, 90s or 1:30. A bare number is minutes"
+        )]
+        break_: Option<Duration>,
+
+        #[arg(long, help = "Number of work/break cycles to run")]
+        cycles: Option<u64>,
+
+        #[arg(
+            long,
+            value_parser = parse_until,
+            help = "Run as many cycles as fit before this time of day, e.g. 17:30"
+        )]
+        until: Option<DateTime<Local>>,
+
+        #[arg(long, help = "The project the work is for, e.g. a client")]
+        project: Option<String>,
+
+        #[arg(
+            long = "tag",
+            value_name = "TAG",
+            help = "A tag for the sessions. Can be given several times"
+        )]
+        tags: Vec<String>,
+
+        #[arg(long, help = "A note for the sessions, instead of being asked for one")]
+        note: Option<String>,
+    },
+    /// Show the sessions, newest first, with their notes.
+    History {
+        #[arg(
+            long,
+            help = "Only show sessions whose note, project or tags contain this"
+        )]
+        search: Option<String>,
+
+        #[arg(long, help = "The most sessions to show")]
+        limit: Option<usize>,
+    },
+    /// List the sessions with their ids, oldest first, to edit or remove them.
+    Log {
+        #[command(subcommand)]
+        command: Option<LogCommand>,
+
+        #[arg(long, help = "Only list this many of the newest sessions")]
+        limit: Option<usize>,
+    },
+    /// Export the history of your sessions, e.g. to a spreadsheet.
+    Export {
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+
+        #[arg(long, help = "The file to write to, instead of the standard output")]
+        file: Option<PathBuf>,
+
+        #[arg(long, value_parser = parse_date, help = "The first day to export, e.g. 2025-01-31")]
+        from: Option<NaiveDate>,
+
+        #[arg(long, value_parser = parse_date, help = "The last day to export, e.g. 2025-12-31")]
+        to: Option<NaiveDate>,
     },
+    /// Import sessions from a file made by `tomato export`. Sessions which are already
+    /// recorded are skipped.
+    Import { file: PathBuf },
     /// Change the default work/break times.
     SetDefaults {},
     /// Show the statistics for your pomodoro sessions.
-    Stats {},
+    Stats {
+        #[arg(
+            long,
+            value_enum,
+            help = "Show pomodoros, focus and breaks per local calendar period"
+        )]
+        period: Option<Period>,
+
+        #[arg(long, help = "Show the focus of the last 52 weeks as a heatmap")]
+        heatmap: bool,
+
+        #[arg(
+            long,
+            help = "Show the focus by hour of the day and by day of the week"
+        )]
+        histogram: bool,
+
+        #[arg(long, help = "Print JSON, the same as `--output json`")]
+        json: bool,
+    },
+    /// Resume the paused timer of the daemon. Without a daemon, continue or record a timer
+    /// which was interrupted, e.g. by closing the terminal.
+    Resume {},
+    /// Run the timer in the background, controlled by `start`, `pause`, `resume`, `stop`,
+    /// `skip` and `status`.
+    Daemon {},
+    /// Start a pomodoro in the daemon.
+    Start {
+        #[arg(long, value_parser = parse_duration, help = "Duration of work")]
+        work: Option<Duration>,
+
+        #[arg(long, value_parser = parse_duration, help = "Duration of break")]
+        break_: Option<Duration>,
+    },
+    /// Pause the timer of the daemon.
+    Pause {},
+    /// Stop the timer of the daemon, recording the part of the cycle which was done.
+    Stop {},
+    /// Skip to the next phase of the timer of the daemon.
+    Skip {},
+    /// Show what the timer is doing, e.g. for status bars such as waybar, polybar or tmux.
+    Status {
+        #[arg(
+            long,
+            default_value = status::DEFAULT_FORMAT,
+            help = "Template with {phase}, {state}, {icon}, {remaining}, {percentage} and {count}"
+        )]
+        format: String,
+
+        #[arg(long, help = "Print JSON for a waybar custom module")]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum LogCommand {
+    /// Record a pomodoro which was done away from the terminal.
+    Add {
+        #[arg(
+            long,
+            value_parser = parse_at,
+            help = "When the work was done, e.g. \"2025-01-31 14:00\" or 14:00 for today. Defaults to now"
+        )]
+        at: Option<DateTime<Local>>,
+
+        #[arg(
+            long,
+            value_parser = parse_duration,
+            help = "Duration of work. Defaults to the work time of the settings"
+        )]
+        work: Option<Duration>,
+
+        #[arg(
+            long,
+            value_parser = parse_duration,
+            help = "Duration of break. Defaults to the break time of the settings"
+        )]
+        break_: Option<Duration>,
+
+        #[arg(long, help = "The project the work was for")]
+        project: Option<String>,
+
+        #[arg(
+            long = "tag",
+            value_name = "TAG",
+            help = "A tag for the session. Can be given several times"
+        )]
+        tags: Vec<String>,
+
+        #[arg(long)]
+        note: Option<String>,
+    },
+    /// Change a session. Only the given fields are changed, and an empty text clears one.
+    Edit {
+        #[arg(help = "The id of the session, as listed by `tomato log`")]
+        id: usize,
+
+        #[arg(long, value_parser = parse_duration, help = "Duration of work")]
+        work: Option<Duration>,
+
+        #[arg(long, value_parser = parse_duration, help = "Duration of break")]
+        break_: Option<Duration>,
+
+        #[arg(long, value_enum)]
+        status: Option<SessionStatus>,
+
+        #[arg(long)]
+        project: Option<String>,
+
+        #[arg(long, help = "Tags separated by commas, replacing the current ones")]
+        tags: Option<String>,
+
+        #[arg(long)]
+        note: Option<String>,
+    },
+    /// Remove a session.
+    Rm {
+        #[arg(help = "The id of the session, as listed by `tomato log`")]
+        id: usize,
+
+        #[arg(long, help = "Remove the session without asking")]
+        yes: bool,
+    },
+}
+
+/// Describes the session with the given id, for the messages of `tomato log`.
+/// Exits with an error if there is no session with the id.
+fn describe_session(sessions: &SessionList, id: usize) -> String {
+    let Some(session) = sessions.get(id) else {
+        eprintln!("There is no session with id {id}. See `tomato log`.");
+        std::process::exit(1);
+    };
+
+    format!(
+        "session {id} from {} ({} of work)",
+        session
+            .timestamp
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M"),
+        format_duration(session.work_seconds)
+    )
+}
+
+/// Carries out a `tomato log` command, and writes the changed sessions to
+/// `sessions.jsonl`. Exits with an error if there is no session with the id.
+fn run_log_command(command: &LogCommand, sessions: &mut SessionList, settings: &Settings) {
+    match command {
+        LogCommand::Add {
+            at,
+            work,
+            break_,
+            project,
+            tags,
+            note,
+        } => {
+            let work_seconds = work.map_or(settings.work_time * 60, |work| work.as_secs());
+            let break_seconds = break_.map_or(settings.break_time * 60, |break_| break_.as_secs());
+            let timestamp = at.unwrap_or_else(Local::now).with_timezone(&Utc);
+
+            let mut session = Session::new(Some(timestamp), work_seconds, break_seconds)
+                .with_labels(
+                    project.clone().filter(|project| !project.trim().is_empty()),
+                    tags.iter().flat_map(|tag| parse_tags(tag)).collect(),
+                );
+            session.note = note.clone().filter(|note| !note.trim().is_empty());
+
+            let id = timers::change_sessions(sessions, |sessions| sessions.insert(session));
+            println!("Added {}.", describe_session(sessions, id));
+        }
+        LogCommand::Edit {
+            id,
+            work,
+            break_,
+            status,
+            project,
+            tags,
+            note,
+        } => {
+            let described = describe_session(sessions, *id);
+            let changes = Changes {
+                work_seconds: work.map(|work| work.as_secs()),
+                break_seconds: break_.map(|break_| break_.as_secs()),
+                status: *status,
+                project: project.clone(),
+                tags: tags.clone(),
+                note: note.clone(),
+            };
+            if changes.is_empty() {
+                println!("Nothing to change. See `tomato log edit --help`.");
+                return;
+            }
+
+            // Another instance of Tomato may have recorded sessions since the
+            // ids were listed, so the session is found again by what it is.
+            let target = sessions.get(*id).cloned();
+            let changed = timers::change_sessions(sessions, |sessions| {
+                let id = target.and_then(|target| sessions.id_of(&target));
+                let session = id.and_then(|id| sessions.get_mut(id));
+                session.map(|session| changes.apply(session)).is_some()
+            });
+            if !changed {
+                exit_session_gone(&described);
+            }
+            println!("Changed {described}.");
+        }
+        LogCommand::Rm { id, yes } => {
+            let described = describe_session(sessions, *id);
+            if !yes {
+                println!("Remove {described}? [y/N]");
+                let mut answer = String::new();
+                let _ = io::stdin().read_line(&mut answer);
+                if !answer.trim().eq_ignore_ascii_case("y") {
+                    println!("Nothing was removed.");
+                    return;
+                }
+            }
+
+            let target = sessions.get(*id).cloned();
+            let removed = timers::change_sessions(sessions, |sessions| {
+                let id = target.and_then(|target| sessions.id_of(&target));
+                id.and_then(|id| sessions.remove(id)).is_some()
+            });
+            if !removed {
+                exit_session_gone(&described);
+            }
+            println!("Removed {described}.");
+        }
+    }
+}
+
+/// Exits with an error, as the session which was to be changed was removed
+/// by another instance of Tomato in the meantime.
+fn exit_session_gone(described: &str) -> ! {
+    eprintln!("Nothing was changed, as {described} was removed in the meantime.");
+    std::process::exit(1);
+}
+
+/// Sends a command to the daemon and prints its answer. Exits with an
+/// error if the daemon is not running, or refused the command.
+fn send_to_daemon(request: Request) {
+    match daemon::send(&request) {
+        Ok(response) if response.ok => println!("{}", response.message),
+        Ok(response) => {
+            eprintln!("{}", response.message);
+            std::process::exit(1);
+        }
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        Err(_) => {
+            eprintln!("The Tomato daemon is not running. Start it with `tomato daemon`.");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Parses a date, such as `2025-01-31`.
+fn parse_date(input: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(input, "%Y-%m-%d")
+        .map_err(|_| format!("'{input}' is not a date like 2025-01-31"))
+}
+
+/// Parses the `--at` argument of `tomato log add`.
+fn parse_at(input: &str) -> Result<DateTime<Local>, String> {
+    past_local_time(input, Local::now())
+}
+
+/// Reads a local date and time such as `2025-01-31 14:00`, or a time of day
+/// such as `14:00`, which is today. The time may not be after `now`.
+fn past_local_time(input: &str, now: DateTime<Local>) -> Result<DateTime<Local>, String> {
+    let time = NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M")
+        .or_else(|_| {
+            NaiveTime::parse_from_str(input, "%H:%M").map(|time| now.date_naive().and_time(time))
+        })
+        .map_err(|_| format!("'{input}' is not a time like \"2025-01-31 14:00\" or 14:00"))?
+        .and_local_timezone(Local)
+        .earliest()
+        .ok_or_else(|| format!("{input} does not exist in the local timezone"))?;
+
+    if time > now {
+        return Err(format!("{input} is in the future"));
+    }
+    Ok(time)
+}
+
+/// Parses the `--until` argument, a time of day such as `17:30`.
+fn parse_until(input: &str) -> Result<DateTime<Local>, String> {
+    next_time_of_day(input, Local::now())
+}
+
+/// Finds the next time the clock shows `input` after `now`. If that time
+/// has already passed today, it is tomorrow.
+fn next_time_of_day(input: &str, now: DateTime<Local>) -> Result<DateTime<Local>, String> {
+    let time = NaiveTime::parse_from_str(input, "%H:%M")
+        .map_err(|_| format!("'{input}' is not a time of day like 17:30"))?;
+
+    let mut date = now.date_naive();
+    if time <= now.time() {
+        date += TimeDelta::days(1);
+    }
+
+    date.and_time(time)
+        .and_local_timezone(Local)
+        .earliest()
+        .ok_or_else(|| format!("{input} does not exist in the local timezone"))
 }
 
 pub fn parse_opts(sessions: &mut SessionList, settings: &mut Settings) {
     let opts = Opts::parse();
 
-    let mut timer: Timer = Timer {
-        work_minutes: settings.work_time,
-        break_minutes: settings.break_time,
-        total_worked_minutes: sessions.total_work_minutes(),
-    };
+    let mut timer: Timer = Timer::from_settings(settings, sessions.total_work_seconds());
+
+    // Before a timer is run or stats are shown, the user is told about an
+    // unfinished timer. The menu offers to resume it by itself, and other
+    // commands would be cluttered by the hint.
+    let hint_unfinished = matches!(
+        opts.command,
+        Some(Command::Run { .. } | Command::Stats { .. })
+    );
+    if hint_unfinished && CurrentState::unfinished().is_some() {
+        // Printed to stderr, so that it does not break JSON output.
+        eprintln!("An unfinished timer was found. Run `tomato resume` to continue or record it.\n");
+    }
 
     match &opts.command {
         Some(Command::Run {
             work: work_time,
             break_: break_time,
+            cycles,
+            until,
+            project,
+            tags,
+            note,
         }) => {
-            let work_time = work_time.unwrap_or(settings.work_time);
-            let break_time = break_time.unwrap_or(settings.break_time);
-            let mut timer: Timer = Timer {
-                work_minutes: work_time,
-                break_minutes: break_time,
-                total_worked_minutes: sessions.total_work_minutes(),
+            let mut timer: Timer = Timer::from_settings(settings, sessions.total_work_seconds());
+            if let Some(work_time) = work_time {
+                timer.set_work_seconds(work_time.as_secs());
+            }
+            if let Some(break_time) = break_time {
+                timer.set_break_seconds(break_time.as_secs());
+            }
+            timer.set_labels(
+                project.clone(),
+                tags.iter().flat_map(|tag| parse_tags(tag)).collect(),
+            );
+            timer.note = note.clone();
+            if cycles.is_none() && until.is_none() {
+                ui::start_cycle(&mut timer, sessions, settings);
+            } else {
+                ui::run_block(&mut timer, sessions, settings, *cycles, *until);
+            }
+        }
+        Some(Command::History { search, limit }) => {
+            let history = History::new(sessions, search.as_deref().unwrap_or(""), *limit);
+            render::print(&history, opts.output);
+        }
+        Some(Command::Log { command, limit }) => match command {
+            Some(command) => run_log_command(command, sessions, settings),
+            None => render::print(&Log::new(sessions, *limit), opts.output),
+        },
+        Some(Command::Export {
+            format,
+            file,
+            from,
+            to,
+        }) => {
+            let selected = export::select(sessions, *from, *to);
+            let exported = match format {
+                Format::Csv => export::to_csv(&selected),
+                Format::Ics => Ok(export::to_ics(&selected, Utc::now())),
             };
-            ui::start_cycle(&mut timer, sessions, settings);
+            let written = exported
+                .map_err(|e| e.to_string())
+                .and_then(|contents| match file {
+                    Some(file) => fs::write(file, contents).map_err(|e| e.to_string()),
+                    None => {
+                        print!("{contents}");
+                        Ok(())
+                    }
+                });
+
+            if let Err(e) = written {
+                eprintln!("Could not export the sessions: {e}");
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Import { file }) => {
+            let imported = fs::read_to_string(file)
+                .map_err(|e| e.to_string())
+                .and_then(|contents| export::from_csv(&contents));
+
+            match imported {
+                Ok(imported) => {
+                    let total = imported.len();
+                    let added =
+                        timers::change_sessions(sessions, |sessions| sessions.merge(imported));
+                    println!(
+                        "Imported {added} sessions, skipped {} which were already recorded.",
+                        total - added
+                    );
+                }
+                Err(e) => {
+                    eprintln!("Could not import {}: {e}", file.display());
+                    std::process::exit(1);
+                }
+            }
         }
         Some(Command::SetDefaults {}) => {
             ui::user_input(&mut timer, settings);
         }
-        Some(Command::Stats {}) => {
-            ui::stats(&mut timer);
+        Some(Command::Stats {
+            period,
+            heatmap,
+            histogram,
+            json,
+        }) => {
+            let output = if *json { Output::Json } else { opts.output };
+            let today = Local::now().date_naive();
+
+            if *histogram {
+                render::print(&Histograms::new(sessions), output);
+            } else if *heatmap {
+                render::print(&Heatmap::new(sessions, today), output);
+            } else if let Some(period) = period {
+                render::print(&Breakdown::new(sessions, *period), output);
+            } else {
+                render::print(&Summary::new(sessions, settings, today), output);
+            }
+        }
+        Some(Command::Resume {}) => {
+            if daemon::is_running() {
+                send_to_daemon(Request::Resume);
+            } else {
+                ui::resume(sessions, settings);
+            }
+        }
+        Some(Command::Daemon {}) => {
+            if let Err(e) = daemon::run() {
+                eprintln!("Could not start the daemon: {e}");
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Start {
+            work: work_time,
+            break_: break_time,
+        }) => send_to_daemon(Request::Start {
+            work_seconds: work_time.map(|work_time| work_time.as_secs()),
+            break_seconds: break_time.map(|break_time| break_time.as_secs()),
+        }),
+        Some(Command::Pause {}) => send_to_daemon(Request::Pause),
+        Some(Command::Stop {}) => send_to_daemon(Request::Stop),
+        Some(Command::Skip {}) => send_to_daemon(Request::Skip),
+        Some(Command::Status { format, json }) => {
+            let count = sessions.pomodoros_on(Local::now().date_naive());
+            let state = status::current_state();
+            let status = Status::new(state.as_ref(), count, Utc::now());
+
+            if *json {
+                println!("{}", status.to_waybar_json(format));
+            } else {
+                let view = StatusView {
+                    status: &status,
+                    template: format,
+                };
+                render::print(&view, opts.output);
+            }
         }
         None => {
             ui::ui_loop(sessions, settings);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2025, 1, day, hour, minute, 0)
+            .single()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_next_time_of_day_later_today() {
+        assert_eq!(next_time_of_day("17:30", at(1, 9, 0)), Ok(at(1, 17, 30)));
+    }
+
+    #[test]
+    fn test_next_time_of_day_already_passed_is_tomorrow() {
+        assert_eq!(next_time_of_day("08:15", at(1, 9, 0)), Ok(at(2, 8, 15)));
+        assert_eq!(next_time_of_day("09:00", at(1, 9, 0)), Ok(at(2, 9, 0)));
+    }
+
+    #[test]
+    fn test_past_local_time() {
+        assert_eq!(
+            past_local_time("2025-01-01 14:00", at(2, 9, 0)),
+            Ok(at(1, 14, 0))
+        );
+        // A time of day is today.
+        assert_eq!(past_local_time("08:15", at(2, 9, 0)), Ok(at(2, 8, 15)));
+    }
+
+    #[test]
+    fn test_past_local_time_invalid_or_in_the_future() {
+        assert!(past_local_time("10:00", at(2, 9, 0)).is_err());
+        assert!(past_local_time("2025-01-03 08:00", at(2, 9, 0)).is_err());
+        assert!(past_local_time("yesterday", at(2, 9, 0)).is_err());
+    }
+
+    #[test]
+    fn test_next_time_of_day_invalid() {
+        assert!(next_time_of_day("half past five", at(1, 9, 0)).is_err());
+        assert!(next_time_of_day("25:00", at(1, 9, 0)).is_err());
+    }
+}
@@ -0,0 +1,624 @@
+//! # Daemon
+//! This file contains the Tomato daemon, which runs the timer in the
+//! background, and the client which controls it.
+//!
+//! The daemon listens on a Unix domain socket in `~/.tomato/`. Each
+//! connection sends a single command on one line, e.g. `start 1500 300` or
+//! `pause`, and receives a single JSON `Response` on one line.
+//!
+//! Unix domain sockets are only available on Unix-like systems. Elsewhere,
+//! the daemon cannot be started, and the client finds no daemon running.
+
+use std::io::{self, BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::hooks::{self, Context};
+use crate::json_serializable::JsonSerializable;
+use crate::notify;
+use crate::session::{SessionList, SessionStatus};
+use crate::settings::{Hooks, Settings};
+use crate::sound::*;
+use crate::state::{CurrentState, Phase};
+use crate::storage::Storage;
+use crate::timers::{self, Countdown, Timer};
+
+/// The name of the socket the daemon listens on.
+const SOCKET_FILE: &str = "tomato.sock";
+
+/// How often the daemon checks for commands and finished phases.
+const TICK_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How long the client waits for the daemon to answer.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The commands the daemon understands.
+#[derive(Debug, PartialEq)]
+pub enum Request {
+    // Starts a cycle. Durations which are left out use the settings.
+    Start {
+        work_seconds: Option<u64>,
+        break_seconds: Option<u64>,
+    },
+    Pause,
+    Resume,
+    // Stops the cycle, recording the part of it which was done.
+    Stop,
+    // Ends the current phase early, and moves on to the next.
+    Skip,
+    Status,
+}
+
+impl Request {
+    /// Parses a command, as sent over the socket.
+    pub fn parse(line: &str) -> Result<Request, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+
+        let mut seconds = || match words.next() {
+            Some("-") | None => Ok(None),
+            Some(word) => word
+                .parse::<u64>()
+                .map(Some)
+                .map_err(|_| format!("'{word}' is not a number of seconds")),
+        };
+
+        match command {
+            "start" => Ok(Request::Start {
+                work_seconds: seconds()?,
+                break_seconds: seconds()?,
+            }),
+            "pause" => Ok(Request::Pause),
+            "resume" => Ok(Request::Resume),
+            "stop" => Ok(Request::Stop),
+            "skip" => Ok(Request::Skip),
+            "status" => Ok(Request::Status),
+            _ => Err(format!("Unknown command '{command}'")),
+        }
+    }
+
+    /// Formats the command, to be sent over the socket.
+    pub fn to_line(&self) -> String {
+        let seconds = |seconds: &Option<u64>| match seconds {
+            Some(seconds) => seconds.to_string(),
+            None => "-".to_string(),
+        };
+
+        match self {
+            Request::Start {
+                work_seconds,
+                break_seconds,
+            } => format!("start {} {}", seconds(work_seconds), seconds(break_seconds)),
+            Request::Pause => "pause".to_string(),
+            Request::Resume => "resume".to_string(),
+            Request::Stop => "stop".to_string(),
+            Request::Skip => "skip".to_string(),
+            Request::Status => "status".to_string(),
+        }
+    }
+}
+
+/// The answer of the daemon to a command.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Response {
+    pub ok: bool,
+    pub message: String,
+    // The state of the timer after the command. `None` if it is idle.
+    pub state: Option<CurrentState>,
+}
+
+impl JsonSerializable for Response {}
+
+/// A phase which is being counted down by the daemon.
+struct Running {
+    countdown: Countdown,
+    state: CurrentState,
+    // The time the cycle was paused for before this phase.
+    paused_before: u64,
+}
+
+impl Running {
+    /// Brings the state up to `now`, without writing it to disk.
+    fn state_at(&self, now: DateTime<Utc>) -> CurrentState {
+        let mut state = self.state.clone();
+        state.elapsed_seconds = self.countdown.elapsed(now).as_secs();
+        state.paused = self.countdown.is_paused();
+        state.paused_seconds = self.paused_before + self.countdown.paused(now).as_secs();
+        state.updated_at = now;
+        state
+    }
+
+    /// Runs the hook for `event` with the state of the phase at `now`.
+    fn fire_hook(&self, hooks: &Hooks, event: hooks::Event, now: DateTime<Utc>) {
+        hooks::fire(hooks, event, &Context::from_state(&self.state_at(now)));
+    }
+}
+
+/// The timer engine of the daemon. Runs one cycle at a time, recording
+/// sessions the same way the timers in the foreground do.
+struct Engine {
+    sessions: SessionList,
+    settings: Settings,
+    timer: Timer,
+    running: Option<Running>,
+}
+
+impl Engine {
+    fn new(sessions: SessionList, settings: Settings) -> Engine {
+        let timer = Timer::from_settings(&settings, sessions.total_work_seconds());
+
+        Engine {
+            sessions,
+            settings,
+            timer,
+            running: None,
+        }
+    }
+
+    /// Carries out a command at `now`.
+    fn handle(&mut self, request: Request, now: DateTime<Utc>) -> Response {
+        match request {
+            Request::Start {
+                work_seconds,
+                break_seconds,
+            } => {
+                if self.running.is_some() {
+                    return self.respond(false, "A timer is already running.".to_string());
+                }
+
+                self.timer = Timer::from_settings(&self.settings, self.timer.total_worked_seconds);
+                if let Some(work_seconds) = work_seconds {
+                    self.timer.set_work_seconds(work_seconds);
+                }
+                if let Some(break_seconds) = break_seconds {
+                    self.timer.set_break_seconds(break_seconds);
+                }
+
+                self.start_phase(Phase::Work, self.timer.work_seconds, now);
+                self.respond(true, "Started the pomodoro.".to_string())
+            }
+            Request::Pause | Request::Resume => {
+                let pause = request == Request::Pause;
+                let Some(running) = self.running.as_mut() else {
+                    return self.respond(false, "No timer is running.".to_string());
+                };
+
+                if pause {
+                    if !running.countdown.is_paused() {
+                        running.countdown.pause(now);
+                        running.fire_hook(&self.settings.hooks, hooks::Event::Pause, now);
+                    }
+                } else {
+                    running.countdown.resume(now);
+                }
+                timers::save_state(
+                    &mut running.state,
+                    &running.countdown,
+                    running.paused_before,
+                    now,
+                );
+
+                let message = if pause { "Paused." } else { "Resumed." };
+                self.respond(true, message.to_string())
+            }
+            Request::Stop => {
+                if self.running.is_none() {
+                    return self.respond(false, "No timer is running.".to_string());
+                }
+
+                self.stop(now);
+                self.respond(true, "Stopped the timer.".to_string())
+            }
+            Request::Skip => match self.running.as_ref().map(|running| running.state.phase) {
+                // Work which was not finished is no pomodoro, so it does not
+                // earn a break, and counts as stopped.
+                Some(Phase::Work) if !self.is_phase_finished(now) => {
+                    self.stop(now);
+                    self.respond(
+                        true,
+                        "Skipped the rest of the pomodoro, which was recorded as aborted."
+                            .to_string(),
+                    )
+                }
+                Some(Phase::Work) => {
+                    self.end_phase(now);
+                    self.respond(true, "Skipped to the break.".to_string())
+                }
+                Some(Phase::Break) => {
+                    self.end_phase(now);
+                    self.respond(true, "Skipped the break.".to_string())
+                }
+                None => self.respond(false, "No timer is running.".to_string()),
+            },
+            Request::Status => self.respond(true, self.describe(now)),
+        }
+    }
+
+    /// Checks whether the current phase has run out at `now`.
+    fn is_phase_finished(&self, now: DateTime<Utc>) -> bool {
+        self.running
+            .as_ref()
+            .is_some_and(|running| running.countdown.is_finished(now))
+    }
+
+    /// Stops the current phase at `now`, recording the part of the cycle
+    /// which was done.
+    fn stop(&mut self, now: DateTime<Utc>) {
+        let Some(running) = self.running.take() else {
+            return;
+        };
+
+        running.fire_hook(&self.settings.hooks, hooks::Event::Abort, now);
+        let done = running.countdown.elapsed(now).as_secs();
+        self.timer.add_paused(running.countdown.paused(now));
+        match running.state.phase {
+            Phase::Work => self.record_aborted_work(done, now),
+            Phase::Break => self.finish_break(done, SessionStatus::SkippedBreak),
+        }
+        CurrentState::clear();
+    }
+
+    /// Moves on to the next phase, once the current one has run out.
+    fn tick(&mut self, now: DateTime<Utc>) {
+        let Some(running) = self.running.as_mut() else {
+            return;
+        };
+
+        if running.countdown.is_finished(now) {
+            self.end_phase(now);
+        } else if timers::since(running.state.updated_at, now) >= timers::SAVE_INTERVAL {
+            timers::save_state(
+                &mut running.state,
+                &running.countdown,
+                running.paused_before,
+                now,
+            );
+        }
+    }
+
+    /// Starts counting down a phase at `now`.
+    fn start_phase(&mut self, phase: Phase, duration_seconds: u64, now: DateTime<Utc>) {
+        if phase == Phase::Work {
+            self.timer.paused_seconds = 0;
+            self.timer.work_recorded_at = None;
+        }
+
+        let countdown = Countdown::new(Duration::from_secs(duration_seconds), now);
+        let mut state = CurrentState::new(
+            phase,
+            duration_seconds,
+            self.timer.work_seconds,
+            self.timer.paused_seconds,
+        );
+        state.work_recorded_at = self.timer.work_recorded_at;
+        timers::save_state(&mut state, &countdown, self.timer.paused_seconds, now);
+
+        let running = Running {
+            countdown,
+            paused_before: self.timer.paused_seconds,
+            state,
+        };
+        let event = match phase {
+            Phase::Work => hooks::Event::WorkStart,
+            Phase::Break => hooks::Event::BreakStart,
+        };
+        running.fire_hook(&self.settings.hooks, event, now);
+        self.running = Some(running);
+    }
+
+    /// Ends the current phase at `now`, whether it ran out or was skipped.
+    /// A finished pomodoro moves on to its break, and a finished break ends
+    /// the cycle.
+    fn end_phase(&mut self, now: DateTime<Utc>) {
+        let Some(running) = self.running.take() else {
+            return;
+        };
+
+        let event = match running.state.phase {
+            Phase::Work => hooks::Event::WorkEnd,
+            Phase::Break => hooks::Event::BreakEnd,
+        };
+        running.fire_hook(&self.settings.hooks, event, now);
+
+        let done = running.countdown.elapsed(now).as_secs();
+        self.timer.add_paused(running.countdown.paused(now));
+
+        match running.state.phase {
+            Phase::Work => {
+                self.finish_work(done, now);
+
+                let completed = self.sessions.pomodoros_on(Local::now().date_naive());
+                let break_seconds = self.timer.break_seconds_after(completed);
+                self.start_phase(Phase::Break, break_seconds, now);
+            }
+            Phase::Break => {
+                let status = if running.countdown.is_finished(now) {
+                    SessionStatus::Completed
+                } else {
+                    SessionStatus::SkippedBreak
+                };
+                self.finish_break(done, status);
+                CurrentState::clear();
+            }
+        }
+    }
+
+    /// Records the work of a pomodoro, which is done after `worked_seconds`.
+    fn finish_work(&mut self, worked_seconds: u64, now: DateTime<Utc>) {
+        // Until the break is over, the session counts as having skipped it.
+        let mut session = self.timer.new_session(now, worked_seconds);
+        session.status = SessionStatus::SkippedBreak;
+        session.paused_seconds = self.timer.paused_seconds;
+
+        timers::record_session(&mut self.sessions, session);
+        self.timer.work_recorded_at = Some(now);
+        self.timer.add_worked_seconds(worked_seconds);
+
+        if self.settings.notification.enable {
+            notify::send_notification_work();
+        }
+        play_sound_in_background(POMODORO_FINISH);
+    }
+
+    /// Records a pomodoro which was stopped after `worked_seconds`.
+    fn record_aborted_work(&mut self, worked_seconds: u64, now: DateTime<Utc>) {
+        let mut session = self.timer.new_session(now, worked_seconds);
+        session.status = SessionStatus::Aborted;
+        session.paused_seconds = self.timer.paused_seconds;
+
+        timers::record_session(&mut self.sessions, session);
+        self.timer.add_worked_seconds(worked_seconds);
+    }
+
+    /// Records the break of the pomodoro of this cycle, which lasted
+    /// `taken_seconds`.
+    fn finish_break(&mut self, taken_seconds: u64, status: SessionStatus) {
+        timers::record_break(&mut self.sessions, &self.timer, taken_seconds, status);
+
+        if status == SessionStatus::Completed {
+            if self.settings.notification.enable {
+                notify::send_notification_break();
+            }
+            play_sound_in_background(BREAK_FINISH);
+        }
+    }
+
+    /// Describes what the timer is doing at `now`, e.g. `work: 12:34 left`.
+    fn describe(&self, now: DateTime<Utc>) -> String {
+        match &self.running {
+            Some(running) => {
+                let remaining = timers::format_remaining(running.countdown.remaining(now));
+                let paused = if running.countdown.is_paused() {
+                    " (paused)"
+                } else {
+                    ""
+                };
+                format!("{}: {remaining} left{paused}", running.state.phase.name())
+            }
+            None => "idle".to_string(),
+        }
+    }
+
+    /// Builds a response, with the state of the timer as of now.
+    fn respond(&self, ok: bool, message: String) -> Response {
+        let now = Utc::now();
+        let state = self.running.as_ref().map(|running| running.state_at(now));
+
+        Response { ok, message, state }
+    }
+}
+
+/// Plays a sound without blocking the daemon.
+fn play_sound_in_background(sound: &'static [u8]) {
+    thread::spawn(move || play_sound(sound.to_vec(), 2));
+}
+
+/// The storage holding the socket of the daemon.
+fn socket_storage() -> Storage {
+    Storage::new(None, SOCKET_FILE.to_string())
+}
+
+/// Runs the daemon in the foreground, until it is killed.
+///
+/// ## Returns
+/// Err if the socket could not be created, e.g. because another daemon is
+/// already running.
+#[cfg(unix)]
+pub fn run() -> io::Result<()> {
+    let storage = socket_storage();
+    storage.create_folder()?;
+
+    if storage.path().exists() {
+        if UnixStream::connect(storage.path()).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                "The Tomato daemon is already running.",
+            ));
+        }
+        // Left behind by a daemon which was killed.
+        storage.remove()?;
+    }
+
+    let listener = UnixListener::bind(storage.path())?;
+    listener.set_nonblocking(true)?;
+    println!("Tomato daemon listening on {}", storage.path().display());
+
+    let sessions = SessionList::load_sessions(".tomato".to_string());
+    let settings = Settings::load_settings(".tomato".to_string(), "settings.json".to_string());
+    let mut engine = Engine::new(sessions, settings);
+
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if let Err(e) = serve(&mut engine, stream) {
+                    eprintln!("Could not answer a command: {e}");
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(TICK_INTERVAL),
+            Err(e) => eprintln!("Could not accept a connection: {e}"),
+        }
+
+        engine.tick(Utc::now());
+    }
+}
+
+/// Reads a single command from `stream`, and writes the response.
+#[cfg(unix)]
+fn serve(engine: &mut Engine, stream: UnixStream) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+
+    let response = match Request::parse(&line) {
+        Ok(request) => engine.handle(request, Utc::now()),
+        Err(message) => engine.respond(false, message),
+    };
+
+    let mut stream = stream;
+    writeln!(stream, "{}", response.to_json())
+}
+
+/// Sends a command to the daemon.
+///
+/// ## Returns
+/// The response of the daemon, or Err if it is not running.
+#[cfg(unix)]
+pub fn send(request: &Request) -> io::Result<Response> {
+    let mut stream = UnixStream::connect(socket_storage().path())?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    writeln!(stream, "{}", request.to_line())?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+
+    Response::from_json(&line).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "The daemon sent an invalid response.",
+        )
+    })
+}
+
+/// Checks whether the daemon is running, by connecting to its socket.
+#[cfg(unix)]
+pub fn is_running() -> bool {
+    UnixStream::connect(socket_storage().path()).is_ok()
+}
+
+/// The error of the daemon and its client where there are no Unix domain
+/// sockets.
+#[cfg(not(unix))]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "The Tomato daemon only runs on Unix-like systems.",
+    )
+}
+
+/// Runs the daemon, which is not possible on this platform.
+#[cfg(not(unix))]
+pub fn run() -> io::Result<()> {
+    Err(unsupported())
+}
+
+/// Sends a command to the daemon, which never runs on this platform.
+#[cfg(not(unix))]
+pub fn send(_request: &Request) -> io::Result<Response> {
+    Err(unsupported())
+}
+
+/// Checks whether the daemon is running, which it never is on this platform.
+#[cfg(not(unix))]
+pub fn is_running() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+    use crate::settings::Notifications;
+
+    #[test]
+    fn test_request_parse() {
+        assert_eq!(Request::parse("pause\n"), Ok(Request::Pause));
+        assert_eq!(Request::parse("resume"), Ok(Request::Resume));
+        assert_eq!(Request::parse("stop"), Ok(Request::Stop));
+        assert_eq!(Request::parse("skip"), Ok(Request::Skip));
+        assert_eq!(Request::parse(" status "), Ok(Request::Status));
+        assert_eq!(
+            Request::parse("start"),
+            Ok(Request::Start {
+                work_seconds: None,
+                break_seconds: None
+            })
+        );
+        assert_eq!(
+            Request::parse("start - 90"),
+            Ok(Request::Start {
+                work_seconds: None,
+                break_seconds: Some(90)
+            })
+        );
+    }
+
+    #[test]
+    fn test_request_parse_invalid() {
+        assert!(Request::parse("").is_err());
+        assert!(Request::parse("explode").is_err());
+        assert!(Request::parse("start soon").is_err());
+    }
+
+    #[test]
+    fn test_request_to_line_and_back() {
+        for request in [
+            Request::Start {
+                work_seconds: Some(1500),
+                break_seconds: None,
+            },
+            Request::Pause,
+            Request::Resume,
+            Request::Stop,
+            Request::Skip,
+            Request::Status,
+        ] {
+            assert_eq!(Request::parse(&request.to_line()), Ok(request));
+        }
+    }
+
+    #[test]
+    fn test_skipping_unfinished_work_records_it_as_aborted() {
+        let mut settings = Settings::new(25, 5, Notifications::default());
+        settings.notification.enable = false;
+        let mut engine = Engine::new(SessionList::new(None), settings);
+        let now = Utc::now();
+
+        let start = Request::Start {
+            work_seconds: Some(25 * 60),
+            break_seconds: Some(5 * 60),
+        };
+        assert!(engine.handle(start, now).ok);
+        assert!(
+            engine
+                .handle(Request::Skip, now + TimeDelta::seconds(10))
+                .ok
+        );
+
+        // No break follows, and the work is no pomodoro.
+        assert!(engine.running.is_none());
+        let session = engine.sessions.iter().last().unwrap();
+        assert_eq!(session.status, SessionStatus::Aborted);
+        assert_eq!(session.work_seconds, 10);
+        assert_eq!(engine.sessions.pomodoros_on(Local::now().date_naive()), 0);
+
+        let _ = std::fs::remove_dir_all(socket_storage().path().parent().unwrap());
+    }
+}
@@ -0,0 +1,242 @@
+//! # Duration
+//! This file contains the parsing and formatting of human-friendly durations,
+//! such as `25m`, `90s`, `1h30m` or `1:30:00`.
+
+use chrono::{DateTime, TimeDelta, TimeZone};
+use std::time::Duration;
+
+/// The longest duration which is accepted. Anything longer is a typo, and
+/// would not fit in the dates it is added to.
+pub const MAX_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Parses a human-friendly duration.
+///
+/// The following formats are accepted:
+/// * A bare number, which is a number of minutes, e.g. `25`.
+/// * Numbers with the units `h`, `m` and `s`, e.g. `90s`, `25m` or `1h30m`.
+/// * A clock-like `h:mm:ss` or `mm:ss`, e.g. `1:30:00` or `25:00`.
+///
+/// ```
+/// assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(90 * 60)));
+/// assert_eq!(parse_duration("1:30:00"), Ok(Duration::from_secs(90 * 60)));
+/// ```
+///
+/// Durations longer than `MAX_DURATION` are rejected.
+///
+/// ## Returns
+/// The parsed duration, or an error message fit for the command line.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let invalid = || format!("'{input}' is not a duration like 25m, 90s, 1h30m or 1:30:00");
+
+    if input.is_empty() {
+        return Err(invalid());
+    }
+
+    let duration = if let Ok(minutes) = input.parse::<u64>() {
+        let seconds = minutes.checked_mul(60).ok_or_else(invalid)?;
+        Duration::from_secs(seconds)
+    } else if input.contains(':') {
+        parse_clock(input).ok_or_else(invalid)?
+    } else {
+        parse_units(input).ok_or_else(invalid)?
+    };
+
+    if duration > MAX_DURATION {
+        return Err(format!(
+            "'{input}' is longer than {}",
+            format_duration(MAX_DURATION.as_secs())
+        ));
+    }
+
+    Ok(duration)
+}
+
+/// Parses `h:mm:ss` or `mm:ss`.
+fn parse_clock(input: &str) -> Option<Duration> {
+    let parts = input
+        .split(':')
+        .map(|part| part.parse::<u64>().ok())
+        .collect::<Option<Vec<u64>>>()?;
+
+    let (hours, minutes, seconds) = match parts[..] {
+        [minutes, seconds] => (0, minutes, seconds),
+        [hours, minutes, seconds] if minutes < 60 => (hours, minutes, seconds),
+        _ => return None,
+    };
+
+    if seconds >= 60 {
+        return None;
+    }
+
+    let seconds = hours
+        .checked_mul(3600)?
+        .checked_add(minutes.checked_mul(60)?)?
+        .checked_add(seconds)?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// Parses numbers followed by the units `h`, `m` or `s`, e.g. `1h30m`. Each
+/// unit may only be used once, and in that order.
+fn parse_units(input: &str) -> Option<Duration> {
+    let mut total = 0;
+    let mut number = String::new();
+    let mut units = ['h', 'm', 's'].iter();
+
+    for c in input.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let value = number.parse::<u64>().ok()?;
+        number.clear();
+
+        // Skips past units which were left out, e.g. the `m` in `1h30s`.
+        let unit = units.find(|unit| **unit == c.to_ascii_lowercase())?;
+        let seconds = match unit {
+            'h' => value.checked_mul(3600)?,
+            'm' => value.checked_mul(60)?,
+            _ => value,
+        };
+        total = u64::checked_add(total, seconds)?;
+    }
+
+    // A trailing number without a unit is not allowed, e.g. `1h30`.
+    if !number.is_empty() {
+        return None;
+    }
+
+    Some(Duration::from_secs(total))
+}
+
+/// Formats a number of seconds as a human-friendly duration, in the same
+/// format as `parse_duration` accepts.
+///
+/// ```
+/// assert_eq!(format_duration(90 * 60), "1h30m");
+/// assert_eq!(format_duration(0), "0m");
+/// ```
+pub fn format_duration(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, (seconds % 3600) / 60, seconds % 60);
+    let mut formatted = String::new();
+
+    if hours > 0 {
+        formatted.push_str(&format!("{hours}h"));
+    }
+    if minutes > 0 || (hours == 0 && seconds == 0) {
+        formatted.push_str(&format!("{minutes}m"));
+    }
+    if seconds > 0 {
+        formatted.push_str(&format!("{seconds}s"));
+    }
+
+    formatted
+}
+
+/// Goes back `seconds` from `time`, e.g. from the end of a session to its
+/// start.
+///
+/// ## Returns
+/// The earlier time, or `None` if it is out of the range of dates, e.g.
+/// because a session in an imported file is absurdly long.
+pub fn seconds_before<Tz: TimeZone>(time: DateTime<Tz>, seconds: u64) -> Option<DateTime<Tz>> {
+    let delta = TimeDelta::try_seconds(i64::try_from(seconds).ok()?)?;
+    time.checked_sub_signed(delta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(seconds: u64) -> Result<Duration, String> {
+        Ok(Duration::from_secs(seconds))
+    }
+
+    #[test]
+    fn test_parse_duration_bare_number_is_minutes() {
+        assert_eq!(parse_duration("25"), secs(25 * 60));
+        assert_eq!(parse_duration(" 5 "), secs(5 * 60));
+    }
+
+    #[test]
+    fn test_parse_duration_units() {
+        assert_eq!(parse_duration("90s"), secs(90));
+        assert_eq!(parse_duration("25m"), secs(25 * 60));
+        assert_eq!(parse_duration("1h30m"), secs(90 * 60));
+        assert_eq!(parse_duration("1h30s"), secs(3630));
+        assert_eq!(parse_duration("2H"), secs(2 * 3600));
+        assert_eq!(parse_duration("1h2m3s"), secs(3723));
+    }
+
+    #[test]
+    fn test_parse_duration_clock() {
+        assert_eq!(parse_duration("1:30:00"), secs(90 * 60));
+        assert_eq!(parse_duration("25:00"), secs(25 * 60));
+        assert_eq!(parse_duration("0:01:30"), secs(90));
+    }
+
+    #[test]
+    fn test_parse_duration_invalid() {
+        for input in [
+            "", "m", "1h30", "30m1h", "1m1m", "1d", "1:60:00", "1:00:60", "1:2:3:4", "-5", "1.5h",
+        ] {
+            assert!(parse_duration(input).is_err(), "{input} should not parse");
+        }
+    }
+
+    #[test]
+    fn test_parse_duration_too_long() {
+        for input in [
+            "999999999999999999",
+            "999999999999999999m",
+            "9999999999999999h",
+            "5124095576030431h16s",
+            "9999999999999999:00:00",
+        ] {
+            assert!(parse_duration(input).is_err(), "{input} should not parse");
+        }
+    }
+
+    #[test]
+    fn test_parse_duration_longer_than_a_day() {
+        assert_eq!(parse_duration("24h"), secs(24 * 3600));
+        assert_eq!(parse_duration("1440"), secs(24 * 3600));
+        for input in ["24h1s", "1441", "24:00:01", "25h", "106751991167300h"] {
+            assert_eq!(
+                parse_duration(input),
+                Err(format!("'{input}' is longer than 24h")),
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0), "0m");
+        assert_eq!(format_duration(45), "45s");
+        assert_eq!(format_duration(90), "1m30s");
+        assert_eq!(format_duration(25 * 60), "25m");
+        assert_eq!(format_duration(2 * 3600), "2h");
+        assert_eq!(format_duration(3723), "1h2m3s");
+    }
+
+    #[test]
+    fn test_seconds_before() {
+        let time = DateTime::from_timestamp(1736758800, 0).unwrap();
+
+        assert_eq!(
+            seconds_before(time, 25 * 60),
+            DateTime::from_timestamp(1736757300, 0)
+        );
+        assert_eq!(seconds_before(time, u64::MAX), None);
+        assert_eq!(seconds_before(time, i64::MAX as u64), None);
+        assert_eq!(seconds_before(time, 1 << 50), None);
+    }
+
+    #[test]
+    fn test_format_duration_round_trips() {
+        for seconds in [0, 59, 60, 61, 3599, 3600, 5400, 86399] {
+            assert_eq!(parse_duration(&format_duration(seconds)), secs(seconds));
+        }
+    }
+}
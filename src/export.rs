@@ -0,0 +1,347 @@
+//! # Export
+//! This file contains the export and import of the session history, e.g. to
+//! reconcile focus time with timesheets in a spreadsheet.
+//!
+//! The CSV has a row for every session, with every field of `Session`.
+//! Timestamps are in RFC 3339, durations in seconds, and tags are separated
+//! by commas.
+//!
+//! The iCalendar export has an event for every pomodoro which was not
+//! aborted, so that focus blocks show up in calendar apps.
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::duration::seconds_before;
+use crate::session::{parse_tags, Session, SessionList, SessionStatus};
+
+/// The formats sessions can be exported in.
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum Format {
+    #[default]
+    Csv,
+    Ics,
+}
+
+/// The longest a line of iCalendar may be, in bytes, before it is folded.
+const ICS_LINE_LENGTH: usize = 75;
+
+/// Finds the sessions which were done between two local dates.
+///
+/// ## Arguments
+/// * from: The first date to include, or `None` to start at the beginning.
+/// * to: The last date to include, or `None` to include up to today.
+pub fn select(
+    sessions: &SessionList,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Vec<&Session> {
+    sessions
+        .iter()
+        .filter(|session| {
+            let date = session.timestamp.with_timezone(&Local).date_naive();
+            from.is_none_or(|from| date >= from) && to.is_none_or(|to| date <= to)
+        })
+        .collect()
+}
+
+/// A session, as a row of the CSV.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Row {
+    timestamp: String,
+    work_seconds: u64,
+    break_seconds: u64,
+    paused_seconds: u64,
+    status: SessionStatus,
+    project: Option<String>,
+    tags: String,
+    note: Option<String>,
+}
+
+impl Row {
+    fn from_session(session: &Session) -> Row {
+        Row {
+            timestamp: session.timestamp.to_rfc3339(),
+            work_seconds: session.work_seconds,
+            break_seconds: session.break_seconds,
+            paused_seconds: session.paused_seconds,
+            status: session.status,
+            project: session.project.clone(),
+            tags: session.tags.join(","),
+            note: session.note.clone(),
+        }
+    }
+
+    fn into_session(self) -> Result<Session, String> {
+        let timestamp = DateTime::parse_from_rfc3339(&self.timestamp)
+            .map_err(|e| format!("'{}' is not an RFC 3339 timestamp: {e}", self.timestamp))?
+            .with_timezone(&Utc);
+
+        let mut session = Session::new(Some(timestamp), self.work_seconds, self.break_seconds)
+            .with_labels(
+                self.project.filter(|project| !project.is_empty()),
+                parse_tags(&self.tags),
+            );
+        session.paused_seconds = self.paused_seconds;
+        session.status = self.status;
+        session.note = self.note.filter(|note| !note.is_empty());
+
+        Ok(session)
+    }
+}
+
+/// Writes the sessions as CSV, with a header row.
+pub fn to_csv(sessions: &[&Session]) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for session in sessions {
+        writer
+            .serialize(Row::from_session(session))
+            .map_err(|e| e.to_string())?;
+    }
+
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+/// Reads sessions from CSV, as written by `to_csv`.
+///
+/// ## Returns
+/// The sessions, or an error naming the row which could not be read.
+pub fn from_csv(contents: &str) -> Result<Vec<Session>, String> {
+    let mut reader = csv::Reader::from_reader(contents.as_bytes());
+
+    reader
+        .deserialize::<Row>()
+        .enumerate()
+        .map(|(index, row)| {
+            // The header is the first line, so the first row is line 2.
+            let line = index + 2;
+            row.map_err(|e| e.to_string())
+                .and_then(Row::into_session)
+                .map_err(|e| format!("Could not read row {line}: {e}"))
+        })
+        .collect()
+}
+
+/// Writes the pomodoros which were not aborted as iCalendar events. Each
+/// event ends at the timestamp of the session, and starts as long before it
+/// as the work and the pauses lasted.
+///
+/// ## Arguments
+/// * now: The time the calendar is made at.
+pub fn to_ics(sessions: &[&Session], now: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Tomato//Pomodoro sessions//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+
+    for session in sessions
+        .iter()
+        .filter(|session| session.status != SessionStatus::Aborted)
+    {
+        // Sessions which would start before the earliest date, e.g. from an
+        // imported file, cannot be placed in the calendar.
+        let Some(start) = session
+            .work_seconds
+            .checked_add(session.paused_seconds)
+            .and_then(|length| seconds_before(session.timestamp, length))
+        else {
+            continue;
+        };
+        let summary = match (&session.project, &session.note) {
+            (Some(project), Some(note)) => format!("🍅 {project}: {note}"),
+            (Some(text), None) | (None, Some(text)) => format!("🍅 {text}"),
+            (None, None) => "🍅 Pomodoro".to_string(),
+        };
+
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!(
+            "UID:{}-{}@tomato",
+            session.timestamp.timestamp(),
+            session.work_seconds
+        ));
+        lines.push(format!("DTSTAMP:{}", ics_time(now)));
+        lines.push(format!("DTSTART:{}", ics_time(start)));
+        lines.push(format!("DTEND:{}", ics_time(session.timestamp)));
+        lines.push(format!("SUMMARY:{}", ics_text(&summary)));
+        if !session.tags.is_empty() {
+            let tags: Vec<String> = session.tags.iter().map(|tag| ics_text(tag)).collect();
+            lines.push(format!("CATEGORIES:{}", tags.join(",")));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines
+        .iter()
+        .map(|line| fold_ics_line(line) + "\r\n")
+        .collect()
+}
+
+/// Formats a time in UTC, e.g. `20250113T090000Z`.
+fn ics_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes the characters which have a meaning in iCalendar text.
+fn ics_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Splits a line which is too long into several, each but the first
+/// starting with a space. Lines are only split between characters.
+fn fold_ics_line(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+
+    for c in line.chars() {
+        if length + c.len_utf8() > ICS_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            // The space counts towards the length of the new line.
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn sessions() -> SessionList {
+        let at = |hour| Utc.with_ymd_and_hms(2025, 1, 13, hour, 0, 0).unwrap();
+
+        SessionList::new(Some(vec![
+            Session::new(Some(at(9)), 25 * 60, 5 * 60),
+            Session {
+                paused_seconds: 42,
+                status: SessionStatus::SkippedBreak,
+                note: Some("Fixed the parser, \"finally\"\nand more".to_string()),
+                ..Session::new(Some(at(10)), 50 * 60, 0).with_labels(
+                    Some("acme, inc".to_string()),
+                    vec!["deep".to_string(), "review".to_string()],
+                )
+            },
+        ]))
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let csv = to_csv(&select(&sessions(), None, None)).unwrap();
+        let mut lines = csv.lines();
+
+        assert_eq!(
+            lines.next(),
+            Some("timestamp,work_seconds,break_seconds,paused_seconds,status,project,tags,note")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("2025-01-13T09:00:00+00:00,1500,300,0,completed,,,")
+        );
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let csv = to_csv(&select(&sessions(), None, None)).unwrap();
+        let imported = SessionList::new(Some(from_csv(&csv).unwrap()));
+
+        assert_eq!(imported, sessions());
+    }
+
+    #[test]
+    fn test_select_between_dates() {
+        let sessions = sessions();
+        let day = |day| NaiveDate::from_ymd_opt(2025, 1, day).unwrap();
+        // The sessions are on 2025-01-13 in UTC, which is the same local date
+        // in most timezones, but not all.
+        let local = sessions
+            .iter()
+            .next()
+            .unwrap()
+            .timestamp
+            .with_timezone(&Local)
+            .date_naive();
+
+        assert_eq!(select(&sessions, None, None).len(), 2);
+        assert_eq!(select(&sessions, Some(local), Some(local)).len(), 2);
+        assert_eq!(select(&sessions, Some(day(20)), None).len(), 0);
+        assert_eq!(select(&sessions, None, Some(day(1))).len(), 0);
+    }
+
+    #[test]
+    fn test_to_ics() {
+        let now = Utc.with_ymd_and_hms(2025, 2, 1, 0, 0, 0).unwrap();
+        let sessions = sessions();
+        let mut selected = select(&sessions, None, None);
+        let aborted = Session {
+            status: SessionStatus::Aborted,
+            ..Session::new(Some(now), 60, 0)
+        };
+        selected.push(&aborted);
+
+        let ics = to_ics(&selected, now);
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        // The aborted pomodoro is left out.
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+
+        assert!(ics.contains(
+            "BEGIN:VEVENT\r\nUID:1736758800-1500@tomato\r\nDTSTAMP:20250201T000000Z\r\n\
+             DTSTART:20250113T083500Z\r\nDTEND:20250113T090000Z\r\nSUMMARY:🍅 Pomodoro\r\n\
+             END:VEVENT"
+        ));
+        // Paused time makes the event longer.
+        assert!(ics.contains("DTSTART:20250113T090918Z\r\nDTEND:20250113T100000Z"));
+        assert!(ics.contains("CATEGORIES:deep,review\r\n"));
+        assert!(ics.contains("SUMMARY:🍅 acme\\, inc: Fixed the parser\\, \"finally\"\\nand mo"));
+    }
+
+    #[test]
+    fn test_to_ics_skips_sessions_longer_than_the_range_of_dates() {
+        let now = Utc.with_ymd_and_hms(2025, 2, 1, 0, 0, 0).unwrap();
+        let sessions = [
+            Session::new(Some(now), u64::MAX, 0),
+            Session {
+                paused_seconds: u64::MAX,
+                ..Session::new(Some(now), 60, 0)
+            },
+        ];
+
+        let ics = to_ics(&sessions.iter().collect::<Vec<&Session>>(), now);
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 0);
+    }
+
+    #[test]
+    fn test_fold_ics_line() {
+        let line = format!("SUMMARY:{}", "a".repeat(100));
+        let folded = fold_ics_line(&line);
+        let lines: Vec<&str> = folded.split("\r\n").collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), 75);
+        assert!(lines[1].starts_with(' '));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+        assert_eq!(fold_ics_line("SUMMARY:short"), "SUMMARY:short");
+    }
+
+    #[test]
+    fn test_from_csv_reports_the_row() {
+        let csv = "timestamp,work_seconds,break_seconds,paused_seconds,status,project,tags,note\n\
+                   2025-01-13T09:00:00Z,1500,300,0,completed,,,\n\
+                   yesterday,1500,300,0,completed,,,\n";
+
+        let error = from_csv(csv).unwrap_err();
+        assert!(error.starts_with("Could not read row 3"), "{error}");
+    }
+}
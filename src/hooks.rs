@@ -0,0 +1,308 @@
+//! # Hooks
+//! This file runs the shell commands the user has set in the `hooks`
+//! section of the settings, when the timer moves between phases.
+//!
+//! Each command is run with `sh -c` in the background, so a slow command
+//! never holds up the timer. Commands which fail or time out are logged to
+//! `~/.tomato/hooks.log`. Before Tomato exits, it waits for the commands
+//! which are still running, so that they are not stopped along with it.
+
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+
+use crate::settings::Hooks;
+use crate::state::CurrentState;
+use crate::storage::Storage;
+
+/// The name of the file failed hooks are logged to.
+const LOG_FILE: &str = "hooks.log";
+
+/// How often a running hook is checked for having finished.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long after its timeout a hook is waited for, to kill it and log it.
+const KILL_MARGIN: Duration = Duration::from_secs(1);
+
+/// A hook which was started in the background.
+struct Pending {
+    thread: JoinHandle<()>,
+    // When the hook is no longer waited for.
+    deadline: Instant,
+}
+
+/// The hooks which may still be running.
+static PENDING: Mutex<Vec<Pending>> = Mutex::new(Vec::new());
+
+/// The events hooks can be set for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    WorkStart,
+    WorkEnd,
+    BreakStart,
+    BreakEnd,
+    Pause,
+    Abort,
+}
+
+impl Event {
+    /// The name of the event, as used in the settings.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::WorkStart => "work_start",
+            Event::WorkEnd => "work_end",
+            Event::BreakStart => "break_start",
+            Event::BreakEnd => "break_end",
+            Event::Pause => "pause",
+            Event::Abort => "abort",
+        }
+    }
+
+    /// Finds the command set for the event, if any.
+    fn command<'a>(&self, hooks: &'a Hooks) -> Option<&'a str> {
+        let command = match self {
+            Event::WorkStart => &hooks.work_start,
+            Event::WorkEnd => &hooks.work_end,
+            Event::BreakStart => &hooks.break_start,
+            Event::BreakEnd => &hooks.break_end,
+            Event::Pause => &hooks.pause,
+            Event::Abort => &hooks.abort,
+        };
+
+        command
+            .as_deref()
+            .filter(|command| !command.trim().is_empty())
+    }
+}
+
+/// Describes the session a hook is run for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Context {
+    // `work` or `break`.
+    pub phase: &'static str,
+    pub duration_seconds: u64,
+    pub elapsed_seconds: u64,
+    pub work_seconds: u64,
+    pub paused_seconds: u64,
+}
+
+impl Context {
+    /// Creates the context from the state of the running timer.
+    pub fn from_state(state: &CurrentState) -> Context {
+        Context {
+            phase: state.phase.name(),
+            duration_seconds: state.duration_seconds,
+            elapsed_seconds: state.elapsed_seconds,
+            work_seconds: state.work_seconds,
+            paused_seconds: state.paused_seconds,
+        }
+    }
+
+    /// The environment variables the command is run with.
+    fn environment(&self, event: Event) -> Vec<(&'static str, String)> {
+        vec![
+            ("TOMATO_EVENT", event.name().to_string()),
+            ("TOMATO_PHASE", self.phase.to_string()),
+            ("TOMATO_DURATION", self.duration_seconds.to_string()),
+            ("TOMATO_ELAPSED", self.elapsed_seconds.to_string()),
+            (
+                "TOMATO_REMAINING",
+                self.duration_seconds
+                    .saturating_sub(self.elapsed_seconds)
+                    .to_string(),
+            ),
+            ("TOMATO_WORK", self.work_seconds.to_string()),
+            ("TOMATO_PAUSED", self.paused_seconds.to_string()),
+        ]
+    }
+}
+
+/// Runs the command set for `event` in the background, if there is one.
+pub fn fire(hooks: &Hooks, event: Event, context: &Context) {
+    let Some(command) = event.command(hooks) else {
+        return;
+    };
+
+    let command = command.to_string();
+    let environment = context.environment(event);
+    let timeout = Duration::from_secs(hooks.timeout_seconds);
+
+    let thread = thread::spawn(move || {
+        if let Err(message) = execute(&command, &environment, timeout) {
+            log_failure(event, &command, &message);
+        }
+    });
+
+    let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+    pending.retain(|hook| !hook.thread.is_finished());
+    pending.push(Pending {
+        thread,
+        deadline: Instant::now() + timeout + KILL_MARGIN,
+    });
+}
+
+/// Waits for the hooks which are still running, before Tomato exits. A hook
+/// is waited for until it has been killed for running too long, and its
+/// failure has been logged, but no longer.
+pub fn wait_for_pending() {
+    let pending = std::mem::take(&mut *PENDING.lock().unwrap_or_else(|e| e.into_inner()));
+
+    for hook in pending {
+        while !hook.thread.is_finished() && Instant::now() < hook.deadline {
+            thread::sleep(POLL_INTERVAL);
+        }
+        if hook.thread.is_finished() {
+            let _ = hook.thread.join();
+        }
+    }
+}
+
+/// Runs a command with `sh -c`, and waits for it to finish. The command is
+/// killed if it runs for longer than `timeout`.
+///
+/// ## Returns
+/// Ok(()) if the command succeeded, otherwise Err with a description of
+/// what went wrong.
+fn execute(
+    command: &str,
+    environment: &[(&'static str, String)],
+    timeout: Duration,
+) -> Result<(), String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(environment.iter().map(|(name, value)| (name, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("could not be started: {e}"))?;
+
+    let started = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return Ok(()),
+            Ok(Some(status)) => return Err(format!("failed with {status}")),
+            Ok(None) if started.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("timed out after {} seconds", timeout.as_secs()));
+            }
+            Ok(None) => thread::sleep(POLL_INTERVAL),
+            Err(e) => return Err(format!("could not be waited for: {e}")),
+        }
+    }
+}
+
+/// Appends a failed hook to `hooks.log`. Failing to log is ignored, as the
+/// timer should keep going no matter what.
+fn log_failure(event: Event, command: &str, message: &str) {
+    let line = format!(
+        "{} {} `{}` {}\n",
+        Utc::now().to_rfc3339(),
+        event.name(),
+        command,
+        message
+    );
+
+    let _ = Storage::new(None, LOG_FILE.to_string()).append(line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Phase;
+
+    fn context() -> Context {
+        Context {
+            phase: "work",
+            duration_seconds: 1500,
+            elapsed_seconds: 1400,
+            work_seconds: 1500,
+            paused_seconds: 30,
+        }
+    }
+
+    #[test]
+    fn test_event_command_finds_the_hook() {
+        let hooks = Hooks {
+            work_end: Some("notify-send done".to_string()),
+            pause: Some("  ".to_string()),
+            ..Hooks::default()
+        };
+
+        assert_eq!(Event::WorkEnd.command(&hooks), Some("notify-send done"));
+        assert_eq!(Event::WorkStart.command(&hooks), None);
+        // Blank commands are not run.
+        assert_eq!(Event::Pause.command(&hooks), None);
+    }
+
+    #[test]
+    fn test_context_from_state() {
+        let mut state = CurrentState::new(Phase::Break, 300, 1500, 20);
+        state.elapsed_seconds = 100;
+        let context = Context::from_state(&state);
+
+        assert_eq!(context.phase, "break");
+        assert_eq!(context.elapsed_seconds, 100);
+        assert_eq!(context.work_seconds, 1500);
+        assert_eq!(context.paused_seconds, 20);
+    }
+
+    #[test]
+    fn test_context_environment() {
+        let environment = context().environment(Event::WorkEnd);
+
+        assert!(environment.contains(&("TOMATO_EVENT", "work_end".to_string())));
+        assert!(environment.contains(&("TOMATO_PHASE", "work".to_string())));
+        assert!(environment.contains(&("TOMATO_REMAINING", "100".to_string())));
+        assert!(environment.contains(&("TOMATO_PAUSED", "30".to_string())));
+    }
+
+    #[test]
+    fn test_execute_passes_the_environment() {
+        let environment = context().environment(Event::BreakStart);
+        let command = "test \"$TOMATO_EVENT\" = break_start && test \"$TOMATO_DURATION\" = 1500";
+
+        assert_eq!(
+            execute(command, &environment, Duration::from_secs(5)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_execute_reports_failure() {
+        let result = execute("exit 3", &[], Duration::from_secs(5));
+
+        assert!(result.unwrap_err().contains('3'));
+    }
+
+    #[test]
+    fn test_execute_kills_after_timeout() {
+        let started = Instant::now();
+        let result = execute("sleep 5", &[], Duration::from_millis(200));
+
+        assert_eq!(result, Err("timed out after 0 seconds".to_string()));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_wait_for_pending_lets_the_hook_finish() {
+        let marker = std::env::temp_dir().join(format!("tomato-hook-{}", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+        let hooks = Hooks {
+            abort: Some(format!("sleep 0.3 && touch '{}'", marker.display())),
+            timeout_seconds: 5,
+            ..Hooks::default()
+        };
+
+        fire(&hooks, Event::Abort, &context());
+        wait_for_pending();
+
+        assert!(marker.exists());
+        let _ = std::fs::remove_file(&marker);
+    }
+}
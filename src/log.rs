@@ -0,0 +1,207 @@
+//! # Log
+//! This file contains `tomato log`, which lists the recorded sessions with
+//! their ids, so that single sessions can be fixed with `tomato log edit` or
+//! removed with `tomato log rm`.
+
+use chrono::Local;
+use serde_json::{json, Value};
+
+use crate::duration::format_duration;
+use crate::render::Render;
+use crate::session::{parse_tags, Session, SessionList, SessionStatus};
+
+/// The sessions shown by `tomato log`, with their ids, oldest first.
+pub struct Log<'a> {
+    pub entries: Vec<(usize, &'a Session)>,
+}
+
+impl<'a> Log<'a> {
+    /// Lists the sessions.
+    ///
+    /// ## Arguments
+    /// * limit: Only list this many of the newest sessions.
+    pub fn new(sessions: &'a SessionList, limit: Option<usize>) -> Log<'a> {
+        let mut entries: Vec<(usize, &Session)> = sessions.entries().collect();
+        let skipped = entries.len().saturating_sub(limit.unwrap_or(usize::MAX));
+        entries.drain(..skipped);
+
+        Log { entries }
+    }
+}
+
+impl Render for Log<'_> {
+    fn table(&self) -> String {
+        if self.entries.is_empty() {
+            return "No sessions recorded yet.".to_string();
+        }
+
+        let mut table = format!(
+            "{:>4}  {:<16}  {:>6}  {:>6}  {:<13}  {}\n",
+            "Id", "Done", "Work", "Break", "Status", "Project, tags and note"
+        );
+        for (id, session) in &self.entries {
+            let mut labels = Vec::new();
+            if let Some(project) = &session.project {
+                labels.push(format!("[{project}]"));
+            }
+            labels.extend(session.tags.iter().map(|tag| format!("#{tag}")));
+            if let Some(note) = &session.note {
+                labels.push(note.clone());
+            }
+
+            table.push_str(&format!(
+                "{:>4}  {:<16}  {:>6}  {:>6}  {:<13}  {}\n",
+                id,
+                session
+                    .timestamp
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M"),
+                format_duration(session.work_seconds),
+                format_duration(session.break_seconds),
+                session.status.name(),
+                labels.join(" ")
+            ));
+        }
+
+        table
+    }
+
+    /// A line for each session with its id, timestamp, work, break, status,
+    /// project, tags separated by commas, and note.
+    fn plain(&self) -> String {
+        self.entries
+            .iter()
+            .map(|(id, session)| {
+                format!(
+                    "{id}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                    session.timestamp.to_rfc3339(),
+                    session.work_seconds,
+                    session.break_seconds,
+                    session.status.name(),
+                    session.project.as_deref().unwrap_or(""),
+                    session.tags.join(","),
+                    session
+                        .note
+                        .as_deref()
+                        .unwrap_or("")
+                        .replace(['\t', '\n'], " ")
+                )
+            })
+            .collect()
+    }
+
+    /// The sessions as they are stored in `sessions.jsonl`, with an `id`.
+    fn json(&self) -> Value {
+        let sessions: Vec<Value> = self
+            .entries
+            .iter()
+            .map(|(id, session)| {
+                let mut session = json!(session);
+                session["id"] = json!(id);
+                session
+            })
+            .collect();
+
+        json!({ "sessions": sessions })
+    }
+}
+
+/// The changes `tomato log edit` makes to a session. Fields which are
+/// `None` are left as they are, and empty texts clear the field.
+#[derive(Debug, Default, PartialEq)]
+pub struct Changes {
+    pub work_seconds: Option<u64>,
+    pub break_seconds: Option<u64>,
+    pub status: Option<SessionStatus>,
+    pub project: Option<String>,
+    // Tags separated by commas or spaces, which replace the current tags.
+    pub tags: Option<String>,
+    pub note: Option<String>,
+}
+
+impl Changes {
+    /// Checks whether there is anything to change.
+    pub fn is_empty(&self) -> bool {
+        *self == Changes::default()
+    }
+
+    /// Makes the changes to `session`.
+    pub fn apply(&self, session: &mut Session) {
+        let text = |text: &String| Some(text.trim().to_string()).filter(|text| !text.is_empty());
+
+        if let Some(work_seconds) = self.work_seconds {
+            session.work_seconds = work_seconds;
+        }
+        if let Some(break_seconds) = self.break_seconds {
+            session.break_seconds = break_seconds;
+        }
+        if let Some(status) = self.status {
+            session.status = status;
+        }
+        if let Some(project) = &self.project {
+            session.project = text(project);
+        }
+        if let Some(tags) = &self.tags {
+            session.tags = parse_tags(tags);
+        }
+        if let Some(note) = &self.note {
+            session.note = text(note);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sessions() -> SessionList {
+        SessionList::new(Some(vec![
+            Session::new(None, 10 * 60, 0),
+            Session::new(None, 20 * 60, 0).with_labels(Some("acme".to_string()), Vec::new()),
+            Session::new(None, 30 * 60, 0),
+        ]))
+    }
+
+    #[test]
+    fn test_log_limit_keeps_the_newest() {
+        let sessions = sessions();
+        let log = Log::new(&sessions, Some(2));
+        let ids: Vec<usize> = log.entries.iter().map(|(id, _)| *id).collect();
+
+        assert_eq!(ids, vec![2, 3]);
+        assert_eq!(Log::new(&sessions, None).entries.len(), 3);
+    }
+
+    #[test]
+    fn test_log_json_has_ids() {
+        let sessions = sessions();
+        let json = Log::new(&sessions, Some(1)).json();
+
+        assert_eq!(json["sessions"][0]["id"], 3);
+        assert_eq!(json["sessions"][0]["work_seconds"], 30 * 60);
+    }
+
+    #[test]
+    fn test_changes_apply() {
+        let mut session =
+            Session::new(None, 25 * 60, 5 * 60).with_labels(Some("acme".to_string()), Vec::new());
+        let changes = Changes {
+            work_seconds: Some(20 * 60),
+            status: Some(SessionStatus::Aborted),
+            project: Some(" ".to_string()),
+            tags: Some("deep, review".to_string()),
+            note: Some("Forgot to stop the timer".to_string()),
+            ..Changes::default()
+        };
+        assert!(!changes.is_empty());
+        changes.apply(&mut session);
+
+        assert_eq!(session.work_seconds, 20 * 60);
+        assert_eq!(session.break_seconds, 5 * 60);
+        assert_eq!(session.status, SessionStatus::Aborted);
+        assert_eq!(session.project, None);
+        assert_eq!(session.tags, vec!["deep", "review"]);
+        assert_eq!(session.note, Some("Forgot to stop the timer".to_string()));
+        assert!(Changes::default().is_empty());
+    }
+}
@@ -2,21 +2,32 @@ use crate::session::SessionList;
 use crate::settings::Settings;
 
 mod cli;
+// Only the client of the daemon is used where it cannot run.
+#[cfg_attr(not(unix), allow(dead_code, unused_imports))]
+mod daemon;
+mod duration;
+mod export;
+mod hooks;
 mod json_serializable;
+mod log;
 mod menu;
 mod migration;
 mod notify;
+mod render;
 mod session;
 mod settings;
 mod sound;
+mod state;
+mod stats;
+mod status;
 mod storage;
 mod timers;
 mod ui;
 
 fn main() {
-    let mut sessions =
-        SessionList::load_sessions(".tomato".to_string(), "sessions.json".to_string());
+    let mut sessions = SessionList::load_sessions(".tomato".to_string());
     let mut settings = Settings::load_settings(".tomato".to_string(), "settings.json".to_string());
 
     cli::parse_opts(&mut sessions, &mut settings);
+    hooks::wait_for_pending();
 }
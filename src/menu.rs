@@ -3,8 +3,14 @@ use crossterm::{cursor, execute, terminal};
 use std::io::{self, Write};
 
 use crate::settings::Settings;
+use crate::state::CurrentState;
+use crate::stats;
 
-pub fn print_menu(settings: &mut Settings) {
+/// Prints the menu, with the progress towards today's goal in the header.
+///
+/// ## Arguments
+/// * done_today: The pomodoros done today.
+pub fn print_menu(settings: &mut Settings, done_today: u64) {
     execute!(
         io::stdout(),
         terminal::Clear(terminal::ClearType::All),
@@ -18,6 +24,10 @@ pub fn print_menu(settings: &mut Settings) {
         "==================================================".green()
     );
     println!("{}", "    Tomato - A Terminal Pomodoro Timer".bold().red());
+    println!(
+        "    {}",
+        stats::goal_progress(done_today, settings.daily_goal)
+    );
     println!(
         "{}",
         "==================================================".green()
@@ -29,14 +39,33 @@ pub fn print_menu(settings: &mut Settings) {
     println!();
 
     // Menu options
-    println!("1. Set time for work and break time");
-    println!(
-        "2. Start timer ({}/{})",
-        settings.work_time, settings.break_time
-    );
+    println!("1. Set time for work and breaks");
+    if settings.long_break_interval > 0 {
+        println!(
+            "2. Start timer ({}/{}, {} minute long break every {} pomodoros)",
+            settings.work_time,
+            settings.break_time,
+            settings.long_break_time,
+            settings.long_break_interval
+        );
+    } else {
+        println!(
+            "2. Start timer ({}/{})",
+            settings.work_time, settings.break_time
+        );
+    }
     println!("3. Stats");
     println!("4. Edit Notification Messages");
     println!("5. Toggle Notifications");
+    if CurrentState::unfinished().is_some() {
+        println!("{}", "6. Resume unfinished timer".yellow());
+    }
+    if settings.prompt_for_note {
+        println!("7. Stop asking for notes after pomodoros");
+    } else {
+        println!("7. Ask for notes after pomodoros");
+    }
+    println!("8. History");
     println!("{}", "9. Exit".red());
 
     // User choice prompt
@@ -2,9 +2,13 @@
 //! struct from earlier versions to new versions.
 
 use crate::json_serializable::JsonSerializable;
-use crate::settings::{Notifications, Settings};
+use crate::settings::{
+    Hooks, Notifications, Settings, DEFAULT_DAILY_GOAL, DEFAULT_LONG_BREAK_INTERVAL,
+    DEFAULT_LONG_BREAK_TIME, SETTINGS_VERSION,
+};
 use crate::storage::Storage;
 use regex::Regex;
+use serde_json::{json, Value};
 
 /// Checks if the version of the `settings.json` file is up to date
 /// with the current settings version.
@@ -12,20 +16,104 @@ pub fn is_correct_version(file_contents: &str, settings_version: &str) -> bool {
     find_settings_version(file_contents) == settings_version
 }
 /// Migrates settings from the version of the settings the user is currently
-/// using, to the newest version, by modifying the file in-place.
+/// using, to the newest version, and writes the result to `storage`.
 ///
 ///
 /// ## Arguments
 /// * file_contents: The contents of the `settings.json` file.
-pub fn migrate_settings(file_contents: &str) -> Settings {
-    let version = find_settings_version(file_contents);
-    match version {
-        "0.1" => match migrate_0_1(file_contents) {
-            Ok(settings) => settings,
-            Err(()) => panic!("You have a settings version of 0.1, but it could not be migrated. Please report this incident at GitHub.") 
-        },
-        _ => panic!("Did not find a valid version! Found version {version}"),
+/// * storage: The storage the migrated settings are written to.
+///
+/// ## Returns
+/// The migrated settings, or why they could not be migrated.
+pub fn migrate_settings(file_contents: &str, storage: &Storage) -> Result<Settings, String> {
+    let contents = match upgrade_settings(file_contents) {
+        Ok(contents) => contents,
+        Err(version) if matches!(version.as_str(), "0.1" | "0.2" | "0.3" | "0.4" | "0.5") => {
+            return Err(format!(
+                "the settings have version {version}, but could not be migrated"
+            ))
+        }
+        Err(version) => return Err(format!("did not find a valid version, found {version}")),
+    };
+
+    let settings = Settings::from_json(&contents)
+        .ok_or_else(|| "the migrated settings are not valid".to_string())?;
+
+    // The migrated settings are used even if they cannot be written, and
+    // are migrated again the next time.
+    if let Err(e) = storage.write(settings.to_json()) {
+        eprintln!("Could not write the migrated settings: {e}");
+    }
+
+    Ok(settings)
+}
+
+/// Upgrades the contents of a `settings.json` file one version at a time,
+/// until it reaches `SETTINGS_VERSION`.
+///
+/// ## Arguments
+/// * file_contents: The contents of the `settings.json` file.
+///
+/// ## Returns
+/// The upgraded contents, or the version which could not be migrated.
+fn upgrade_settings(file_contents: &str) -> Result<String, String> {
+    let mut contents = file_contents.to_string();
+
+    loop {
+        let version = find_settings_version(&contents).to_string();
+        let migrated = match version.as_str() {
+            SETTINGS_VERSION => return Ok(contents),
+            "0.1" => migrate_0_1(&contents),
+            "0.2" => migrate_0_2(&contents),
+            "0.3" => migrate_0_3(&contents),
+            "0.4" => migrate_0_4(&contents),
+            "0.5" => migrate_0_5(&contents),
+            _ => Err(()),
+        };
+
+        contents = migrated.map_err(|_| version)?;
+    }
+}
+
+/// Converts sessions stored in whole minutes, in the `work_time` and
+/// `break_time` fields, to seconds in the `work_seconds` and `break_seconds`
+/// fields. Sessions which are already stored in seconds are left as is.
+///
+/// ## Arguments
+/// * file_contents: The contents of the `sessions.json` file of older versions.
+///
+/// ## Returns
+/// The migrated contents. If the contents cannot be parsed, they are
+/// returned unchanged.
+pub fn migrate_sessions(file_contents: &str) -> String {
+    if !file_contents.contains("\"work_time\"") {
+        return file_contents.to_string();
+    }
+
+    let Ok(mut contents) = serde_json::from_str::<Value>(file_contents) else {
+        return file_contents.to_string();
+    };
+
+    let sessions = contents
+        .get_mut("sessions")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut);
+
+    for session in sessions {
+        for (minutes, seconds) in [
+            ("work_time", "work_seconds"),
+            ("break_time", "break_seconds"),
+        ] {
+            if let Some(value) = session.remove(minutes) {
+                let value = value.as_u64().unwrap_or(0) * 60;
+                session.entry(seconds).or_insert(json!(value));
+            }
+        }
     }
+
+    contents.to_string()
 }
 
 /// Finds the version of the settings in the `file_contents` argument.
@@ -47,7 +135,8 @@ fn find_settings_version(file_contents: &str) -> &str {
     caps.get(1).unwrap().as_str()
 }
 
-fn migrate_0_1(settings: &str) -> Result<Settings, ()> {
+/// 0.2 added notifications.
+fn migrate_0_1(settings: &str) -> Result<String, ()> {
     let work_re = Regex::new(r#""work_time":(\d+)"#).unwrap();
     let break_re = Regex::new(r#""break_time":(\d+)"#).unwrap();
     let Some(work_time) = work_re.captures(settings) else {
@@ -57,20 +146,64 @@ fn migrate_0_1(settings: &str) -> Result<Settings, ()> {
         return Err(());
     };
 
-    let storage: Storage = Storage::new(None, "settings.json".to_owned());
+    let notification = serde_json::to_value(Notifications::default()).map_err(|_| ())?;
+
+    let migrated = json!({
+        "version": "0.2",
+        "work_time": work_time.get(1).unwrap().as_str().parse::<u64>().unwrap(),
+        "break_time": break_time.get(1).unwrap().as_str().parse::<u64>().unwrap(),
+        "notification": notification,
+    });
+
+    Ok(migrated.to_string())
+}
+
+/// 0.3 added long breaks.
+fn migrate_0_2(settings: &str) -> Result<String, ()> {
+    add_fields(
+        settings,
+        "0.3",
+        [
+            ("long_break_time", json!(DEFAULT_LONG_BREAK_TIME)),
+            ("long_break_interval", json!(DEFAULT_LONG_BREAK_INTERVAL)),
+        ],
+    )
+}
+
+/// 0.4 added hooks.
+fn migrate_0_3(settings: &str) -> Result<String, ()> {
+    let hooks = serde_json::to_value(Hooks::default()).map_err(|_| ())?;
+    add_fields(settings, "0.4", [("hooks", hooks)])
+}
+
+/// 0.5 added the prompt for notes.
+fn migrate_0_4(settings: &str) -> Result<String, ()> {
+    add_fields(settings, "0.5", [("prompt_for_note", json!(false))])
+}
+
+/// 0.6 added the daily goal.
+fn migrate_0_5(settings: &str) -> Result<String, ()> {
+    add_fields(settings, "0.6", [("daily_goal", json!(DEFAULT_DAILY_GOAL))])
+}
 
-    let return_settings = Settings {
-        version: "0.2".to_owned(),
-        work_time: work_time.get(1).unwrap().as_str().parse::<u64>().unwrap(),
-        break_time: break_time.get(1).unwrap().as_str().parse::<u64>().unwrap(),
-        notification: Notifications::default(),
+/// Adds fields with their default values to the settings, and sets the
+/// version the fields were added in.
+fn add_fields<const N: usize>(
+    settings: &str,
+    version: &str,
+    new_fields: [(&str, Value); N],
+) -> Result<String, ()> {
+    let mut migrated: Value = serde_json::from_str(settings).map_err(|_| ())?;
+    let Some(fields) = migrated.as_object_mut() else {
+        return Err(());
     };
 
-    storage
-        .write(return_settings.to_json())
-        .expect("Could not write to settings file during migration.");
+    fields.insert("version".to_owned(), json!(version));
+    for (name, value) in new_fields {
+        fields.insert(name.to_owned(), value);
+    }
 
-    Ok(return_settings)
+    Ok(migrated.to_string())
 }
 
 #[cfg(test)]
@@ -84,9 +217,74 @@ mod tests {
         assert_eq!(find_settings_version(found_settings), "0.1");
     }
 
-    // TODO: Needs to be implemented once an update rolls out
     #[test]
-    fn test_migrate_settings_migrates_correctly() {}
+    fn test_migrate_settings_migrates_correctly() {
+        let found_settings = "{\"version\":\"0.1\",\"work_time\":50,\"break_time\":10}";
+        let upgraded = upgrade_settings(found_settings).expect("Could not migrate");
+
+        let expected = Settings::new(50, 10, Notifications::default());
+
+        assert_eq!(Settings::from_json(&upgraded), Some(expected));
+    }
+
+    #[test]
+    fn test_migrate_0_2_keeps_notifications() {
+        let found_settings = "{\"version\":\"0.2\",\"work_time\":25,\"break_time\":5,\"notification\":{\"enable\":false,\"work_msg\":\"a\",\"break_msg\":\"b\"}}";
+        let settings = Settings::from_json(&upgrade_settings(found_settings).unwrap()).unwrap();
+
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert!(!settings.notification.enable);
+        assert_eq!(settings.notification.work_msg, "a");
+        assert_eq!(settings.long_break_interval, DEFAULT_LONG_BREAK_INTERVAL);
+    }
+
+    #[test]
+    fn test_migrate_sessions_converts_minutes_to_seconds() {
+        let sessions = "{\"sessions\":[{\"timestamp\":1,\"work_time\":25,\"break_time\":5},{\"timestamp\":2,\"work_seconds\":90,\"break_seconds\":30}]}";
+        let migrated: Value = serde_json::from_str(&migrate_sessions(sessions)).unwrap();
+
+        assert_eq!(
+            migrated,
+            json!({"sessions": [
+                {"timestamp": 1, "work_seconds": 1500, "break_seconds": 300},
+                {"timestamp": 2, "work_seconds": 90, "break_seconds": 30},
+            ]})
+        );
+    }
+
+    #[test]
+    fn test_migrate_sessions_leaves_seconds_alone() {
+        let sessions =
+            "{\"sessions\":[{\"timestamp\":2,\"work_seconds\":90,\"break_seconds\":30}]}";
+        assert_eq!(migrate_sessions(sessions), sessions);
+    }
+
+    #[test]
+    fn test_migrate_0_3_adds_hooks() {
+        let found_settings = "{\"version\":\"0.3\",\"work_time\":25,\"break_time\":5,\"long_break_time\":20,\"long_break_interval\":3,\"notification\":{\"enable\":true,\"work_msg\":\"a\",\"break_msg\":\"b\"}}";
+        let settings = Settings::from_json(&upgrade_settings(found_settings).unwrap()).unwrap();
+
+        assert_eq!(settings.long_break_time, 20);
+        assert_eq!(settings.long_break_interval, 3);
+        assert_eq!(settings.hooks, Hooks::default());
+    }
+
+    #[test]
+    fn test_migrate_0_4_does_not_prompt_for_notes() {
+        let found_settings = "{\"version\":\"0.4\",\"work_time\":25,\"break_time\":5,\"long_break_time\":15,\"long_break_interval\":4,\"notification\":{\"enable\":true,\"work_msg\":\"a\",\"break_msg\":\"b\"},\"hooks\":{\"work_start\":\"true\",\"timeout_seconds\":3}}";
+        let settings = Settings::from_json(&upgrade_settings(found_settings).unwrap()).unwrap();
+
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert_eq!(settings.hooks.work_start, Some("true".to_string()));
+        assert!(!settings.prompt_for_note);
+        assert_eq!(settings.daily_goal, DEFAULT_DAILY_GOAL);
+    }
+
+    #[test]
+    fn test_upgrade_settings_unknown_version() {
+        let found_settings = "{\"version\":\"9.9\",\"work_time\":25,\"break_time\":5}";
+        assert_eq!(upgrade_settings(found_settings), Err("9.9".to_string()));
+    }
 
     #[test]
     fn test_is_correct_version_is_correct() {
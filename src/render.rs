@@ -0,0 +1,80 @@
+//! # Render
+//! This file contains the output formats of the commands which show
+//! information, such as `tomato stats`, `tomato history` and `tomato status`.
+//!
+//! Each of these builds a view, which can be rendered as a table for people,
+//! as tab separated values for scripts, or as JSON for dashboards. The JSON
+//! of each view is documented in the README, and fields are only ever added
+//! to it.
+
+use clap::ValueEnum;
+use serde_json::Value;
+
+/// The formats a view can be rendered in, as given to `--output`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum Output {
+    // Aligned columns and prose, for people.
+    #[default]
+    Table,
+    // Tab separated values without headings or colors, for scripts.
+    Plain,
+    // One line of JSON.
+    Json,
+}
+
+/// Something a command shows, which can be rendered in every `Output`.
+pub trait Render {
+    /// Renders the view for people.
+    fn table(&self) -> String;
+
+    /// Renders the view as lines of tab separated values.
+    fn plain(&self) -> String;
+
+    /// Renders the view as JSON.
+    fn json(&self) -> Value;
+}
+
+/// Renders a view in the format given by `output`.
+pub fn render(view: &dyn Render, output: Output) -> String {
+    match output {
+        Output::Table => view.table(),
+        Output::Plain => view.plain(),
+        Output::Json => view.json().to_string(),
+    }
+}
+
+/// Prints a view in the format given by `output`.
+pub fn print(view: &dyn Render, output: Output) {
+    let rendered = render(view, output);
+    println!("{}", rendered.trim_end());
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    struct Answer;
+
+    impl Render for Answer {
+        fn table(&self) -> String {
+            "Answer: 42".to_string()
+        }
+
+        fn plain(&self) -> String {
+            "answer\t42".to_string()
+        }
+
+        fn json(&self) -> Value {
+            json!({"answer": 42})
+        }
+    }
+
+    #[test]
+    fn test_render_picks_the_output() {
+        assert_eq!(render(&Answer, Output::Table), "Answer: 42");
+        assert_eq!(render(&Answer, Output::Plain), "answer\t42");
+        assert_eq!(render(&Answer, Output::Json), "{\"answer\":42}");
+    }
+}
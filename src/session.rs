@@ -1,17 +1,68 @@
 use chrono::prelude::*;
 use chrono::serde::ts_seconds; // Allows for seralization with Chrono Timestamps
 use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::BufRead;
+use std::path::Path;
 
 use crate::json_serializable::JsonSerializable;
+use crate::migration::migrate_sessions;
 use crate::storage::Storage;
 
+/// The file the sessions are stored in, a session on each line.
+pub const SESSIONS_FILE: &str = "sessions.jsonl";
+
+/// The file older versions stored the sessions in, as a single JSON array.
+const LEGACY_SESSIONS_FILE: &str = "sessions.json";
+
+/// How a session ended.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum SessionStatus {
+    // Both the work and the break were finished.
+    #[default]
+    Completed,
+    // The work was stopped before it was finished.
+    Aborted,
+    // The work was finished, but the break was skipped or stopped early.
+    SkippedBreak,
+}
+
+impl SessionStatus {
+    /// The name of the status, as it is stored.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SessionStatus::Completed => "completed",
+            SessionStatus::Aborted => "aborted",
+            SessionStatus::SkippedBreak => "skipped-break",
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Session {
     #[serde(with = "ts_seconds")] // Converts to a format Serde can (de)serailize
     pub timestamp: DateTime<Utc>, // Has to be UTC, can be converted later
-    pub work_time: u32,
-    pub break_time: u32,
+    pub work_seconds: u64,
+    pub break_seconds: u64,
+    // Seconds the session was paused for. Older sessions were never paused.
+    #[serde(default)]
+    pub paused_seconds: u64,
+    // Sessions from before the status was recorded were all completed.
+    #[serde(default)]
+    pub status: SessionStatus,
+    // The project the work was done for, e.g. a client.
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // What was accomplished in the session, in the user's own words.
+    #[serde(default)]
+    pub note: Option<String>,
 }
 
 /// Holds a list of Session instances.
@@ -26,28 +77,77 @@ impl Session {
     ///
     /// ## Arguments
     /// * timestamp: An optional argument of a `DateTime<Utc>` timestamp. If left to none, it becomes Jan 1, 1970.
-    /// * work_time: The amount of time the user has worked this session.
-    /// * break_time: The amount of time the user has had a break this session.
+    /// * work_seconds: The amount of seconds the user has worked this session.
+    /// * break_seconds: The amount of seconds the user has had a break this session.
     ///
     /// ## Returns
     /// An instance of Session from specified arguments.
-    pub fn new(timestamp: Option<DateTime<Utc>>, work_time: u32, break_time: u32) -> Session {
+    pub fn new(timestamp: Option<DateTime<Utc>>, work_seconds: u64, break_seconds: u64) -> Session {
         match timestamp {
             None => Session {
                 timestamp: Utc
                     .with_ymd_and_hms(1970, 1, 1, 0, 0, 0)
                     .single()
                     .expect("Failed to parse fixed date."),
-                work_time,
-                break_time,
+                work_seconds,
+                break_seconds,
+                paused_seconds: 0,
+                status: SessionStatus::Completed,
+                project: None,
+                tags: Vec::new(),
+                note: None,
             },
+            // Timestamps are stored in whole seconds, so they are kept in
+            // whole seconds from the start.
             Some(timestamp) => Session {
-                timestamp,
-                work_time,
-                break_time,
+                timestamp: timestamp.trunc_subsecs(0),
+                work_seconds,
+                break_seconds,
+                paused_seconds: 0,
+                status: SessionStatus::Completed,
+                project: None,
+                tags: Vec::new(),
+                note: None,
             },
         }
     }
+
+    /// Sets the project and the tags of the session.
+    ///
+    /// ## Arguments
+    /// * project: The project the work was done for, if any.
+    /// * tags: The tags of the session.
+    ///
+    /// ## Returns
+    /// The session, with the project and the tags set.
+    pub fn with_labels(mut self, project: Option<String>, tags: Vec<String>) -> Session {
+        self.project = project;
+        self.tags = tags;
+        self
+    }
+
+    /// Checks whether the note, the project or a tag of the session contains
+    /// `query`, which has to be in lowercase.
+    fn matches(&self, query: &str) -> bool {
+        self.note
+            .iter()
+            .chain(self.project.iter())
+            .chain(self.tags.iter())
+            .any(|text| text.to_lowercase().contains(query))
+    }
+}
+
+/// Splits a list of tags, separated by commas or spaces, e.g. `deep, writing`.
+/// Empty and repeated tags are left out.
+pub fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in input.split([',', ' ']).map(str::trim) {
+        if !tag.is_empty() && !tags.iter().any(|found| found == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+
+    tags
 }
 
 impl JsonSerializable for SessionList {}
@@ -79,37 +179,353 @@ impl SessionList {
         self.sessions.push(session);
     }
 
-    /// Gets the total amount of minutes worked from all Session instances
+    /// Iterates over the sessions, in the order they were recorded.
+    pub fn iter(&self) -> impl Iterator<Item = &Session> {
+        self.sessions.iter()
+    }
+
+    /// Adds sessions, e.g. from an import, leaving out those which are
+    /// already in the list. A session is already in the list if one with the
+    /// same timestamp and work is, so importing twice does not count it
+    /// twice. The sessions are kept in the order of their timestamps.
+    ///
+    /// ## Returns
+    /// The amount of sessions which were added.
+    pub fn merge(&mut self, sessions: Vec<Session>) -> usize {
+        let mut added = 0;
+        for session in sessions {
+            if self.id_of(&session).is_none() {
+                self.sessions.push(session);
+                added += 1;
+            }
+        }
+
+        if added > 0 {
+            self.sessions.sort_by_key(|session| session.timestamp);
+        }
+        added
+    }
+
+    /// Adds a session which was done at any time, e.g. one logged by hand,
+    /// after the sessions which were done before it.
+    ///
+    /// ## Returns
+    /// The id of the session.
+    pub fn insert(&mut self, session: Session) -> usize {
+        let position = self
+            .sessions
+            .partition_point(|recorded| recorded.timestamp <= session.timestamp);
+        self.sessions.insert(position, session);
+        position + 1
+    }
+
+    /// Finds the id of a session in the list. A session is the same as one in
+    /// the list if it was done in the same second, with the same work, even
+    /// if e.g. its break or note differs. Timestamps are stored in whole
+    /// seconds, so a session which was just recorded is found in the list
+    /// read back from `sessions.jsonl`.
+    pub fn id_of(&self, session: &Session) -> Option<usize> {
+        let position = self.sessions.iter().position(|recorded| {
+            recorded.timestamp.timestamp() == session.timestamp.timestamp()
+                && recorded.work_seconds == session.work_seconds
+        })?;
+
+        Some(position + 1)
+    }
+
+    /// Gets the session with the given id. The id of a session is its
+    /// position in the list, in the order the sessions were done, starting
+    /// at 1.
+    pub fn get(&self, id: usize) -> Option<&Session> {
+        self.sessions.get(id.checked_sub(1)?)
+    }
+
+    /// Gets the session with the given id, to update it.
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Session> {
+        self.sessions.get_mut(id.checked_sub(1)?)
+    }
+
+    /// Removes the session with the given id. The sessions after it move up
+    /// one id.
+    ///
+    /// ## Returns
+    /// The removed session, or `None` if there is no session with the id.
+    pub fn remove(&mut self, id: usize) -> Option<Session> {
+        let index = id
+            .checked_sub(1)
+            .filter(|index| *index < self.sessions.len())?;
+        Some(self.sessions.remove(index))
+    }
+
+    /// Iterates over the sessions with their ids.
+    pub fn entries(&self) -> impl Iterator<Item = (usize, &Session)> {
+        self.sessions
+            .iter()
+            .enumerate()
+            .map(|(index, session)| (index + 1, session))
+    }
+
+    /// Gets the most recent session, to update it.
+    ///
+    /// ## Returns
+    /// The last Session instance in `sessions`, or `None` if it is empty.
+    pub fn last_mut(&mut self) -> Option<&mut Session> {
+        self.sessions.last_mut()
+    }
+
+    /// Gets the total amount of seconds worked from all Session instances
     /// in `sessions`.
     ///
     /// ## Returns
-    /// The total amount of minutes worked from all Session instances in
+    /// The total amount of seconds worked from all Session instances in
     /// `sessions` field.
-    pub fn total_work_minutes(&self) -> u64 {
+    pub fn total_work_seconds(&self) -> u64 {
         let mut total: u64 = 0;
         for session in &self.sessions {
-            total += session.work_time as u64;
+            total += session.work_seconds;
         }
 
         total
     }
 
-    /// Finds the sessions from `session.json` and deserializes into the
-    /// `SessionList` struct.
+    /// Counts the pomodoros which were finished on the given local date.
+    ///
+    /// ## Arguments
+    /// * date: A date in the local timezone.
     ///
     /// ## Returns
-    /// * A SessionList struct containing all previous sessions stored in
-    ///   `sessions.json`.
-    pub fn load_sessions(folder: String, file_name: String) -> SessionList {
-        let storage = Storage::new(Some(folder), file_name.clone());
-        let contents = storage.read().unwrap_or_else(|_| "ERR".to_string());
+    /// The amount of sessions with a timestamp on `date`, when converted to
+    /// local time, whose work was not aborted.
+    pub fn pomodoros_on(&self, date: NaiveDate) -> u64 {
+        self.sessions
+            .iter()
+            .filter(|session| session.status != SessionStatus::Aborted)
+            .filter(|session| session.timestamp.with_timezone(&Local).date_naive() == date)
+            .count() as u64
+    }
 
-        if contents.is_empty() || contents == "ERR" {
-            SessionList::new(None)
-        } else {
-            SessionList::from_json(&contents).expect("Could not parse the contents of file.")
+    /// Adds up the seconds worked on each project.
+    ///
+    /// ## Returns
+    /// The seconds worked per project, sorted by the name of the project.
+    /// Sessions without a project are left out.
+    pub fn work_seconds_by_project(&self) -> BTreeMap<String, u64> {
+        let mut totals = BTreeMap::new();
+        for session in &self.sessions {
+            if let Some(project) = &session.project {
+                *totals.entry(project.clone()).or_insert(0) += session.work_seconds;
+            }
         }
+
+        totals
     }
+
+    /// Adds up the seconds worked with each tag. A session with several tags
+    /// counts towards all of them.
+    ///
+    /// ## Returns
+    /// The seconds worked per tag, sorted by the name of the tag.
+    pub fn work_seconds_by_tag(&self) -> BTreeMap<String, u64> {
+        let mut totals = BTreeMap::new();
+        for session in &self.sessions {
+            for tag in &session.tags {
+                *totals.entry(tag.clone()).or_insert(0) += session.work_seconds;
+            }
+        }
+
+        totals
+    }
+
+    /// Finds the sessions whose note, project or tags contain `query`,
+    /// ignoring case. An empty query matches all sessions.
+    ///
+    /// ## Returns
+    /// The matching sessions, newest first.
+    pub fn search(&self, query: &str) -> Vec<&Session> {
+        let query = query.to_lowercase();
+
+        self.sessions
+            .iter()
+            .rev()
+            .filter(|session| query.is_empty() || session.matches(&query))
+            .collect()
+    }
+
+    /// Writes the sessions as JSON Lines, a session on each line.
+    pub fn to_jsonl(&self) -> String {
+        self.sessions
+            .iter()
+            .map(|session| session.to_json() + "\n")
+            .collect()
+    }
+
+    /// Reads sessions from JSON Lines, as written by `to_jsonl`, one line at
+    /// a time. Blank lines are skipped.
+    ///
+    /// ## Returns
+    /// The sessions, or an error naming the line which could not be read.
+    pub fn from_jsonl(lines: impl BufRead) -> Result<SessionList, String> {
+        let mut sessions = Vec::new();
+        for (index, line) in lines.lines().enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let session = Session::from_json(&line)
+                .ok_or_else(|| format!("Could not read line {} of the sessions", index + 1))?;
+            sessions.push(session);
+        }
+
+        Ok(SessionList::new(Some(sessions)))
+    }
+
+    /// Finds the sessions from `sessions.jsonl` in `folder` and deserializes
+    /// them into the `SessionList` struct. The file is locked while it is
+    /// read, so that no other instance of Tomato is halfway through writing
+    /// it. If the folder does not exist yet, there is nothing to lock.
+    ///
+    /// ## Returns
+    /// * A SessionList struct containing all previous sessions.
+    pub fn load_sessions(folder: String) -> SessionList {
+        let storage = Storage::new(Some(folder.clone()), SESSIONS_FILE.to_string());
+        let _lock = storage
+            .path()
+            .parent()
+            .is_some_and(Path::exists)
+            .then(|| storage.lock());
+
+        SessionList::read_sessions(folder)
+    }
+
+    /// Reads the sessions from `sessions.jsonl` in `folder`, without locking
+    /// it, for when the lock is already held.
+    ///
+    /// If there is no `sessions.jsonl` yet, the sessions are moved to it
+    /// from `sessions.json`, where older versions stored them as a single
+    /// JSON array. Sessions stored in whole minutes are converted to seconds.
+    /// `sessions.json` is then renamed to `sessions.json.bak`.
+    ///
+    /// A file which cannot be read is recovered, rather than stopping Tomato.
+    pub fn read_sessions(folder: String) -> SessionList {
+        let storage = Storage::new(Some(folder.clone()), SESSIONS_FILE.to_string());
+
+        match storage.reader() {
+            Ok(reader) => SessionList::from_jsonl(reader).unwrap_or_else(|reason| {
+                SessionList::recover(&storage, &storage, &reason, salvage_lines)
+            }),
+            Err(_) => SessionList::migrate_legacy_file(folder, &storage),
+        }
+    }
+
+    /// Moves the sessions from `sessions.json` to `sessions.jsonl`.
+    fn migrate_legacy_file(folder: String, storage: &Storage) -> SessionList {
+        let legacy = Storage::new(Some(folder), LEGACY_SESSIONS_FILE.to_string());
+        let Ok(contents) = fs::read(legacy.path()) else {
+            return SessionList::new(None);
+        };
+        if contents.is_empty() {
+            return SessionList::new(None);
+        }
+
+        let contents = String::from_utf8_lossy(&contents);
+        let Some(sessions) = SessionList::from_json(&migrate_sessions(&contents)) else {
+            return SessionList::recover(
+                &legacy,
+                storage,
+                "the sessions are not valid JSON",
+                salvage_objects,
+            );
+        };
+        // `sessions.json` is only moved away once its sessions are safely in
+        // `sessions.jsonl`, so that they are moved again the next time.
+        match storage.write(sessions.to_jsonl()) {
+            Ok(_) => {
+                let _ = fs::rename(legacy.path(), legacy.path().with_extension("json.bak"));
+            }
+            Err(e) => eprintln!("Could not move the sessions to {SESSIONS_FILE}: {e}"),
+        }
+
+        sessions
+    }
+
+    /// Salvages the sessions from a file which could not be read, moves the
+    /// file out of the way, tells the user, and writes the salvaged sessions
+    /// to `sessions.jsonl`.
+    ///
+    /// ## Arguments
+    /// * broken: The file which could not be read.
+    /// * storage: `sessions.jsonl`, which may be the broken file.
+    /// * reason: Why the file could not be read.
+    /// * salvage: Finds the sessions in the contents of the broken file.
+    ///
+    /// ## Returns
+    /// The sessions which could be salvaged.
+    fn recover(
+        broken: &Storage,
+        storage: &Storage,
+        reason: &str,
+        salvage: fn(&str) -> Vec<Session>,
+    ) -> SessionList {
+        let contents = fs::read(broken.path()).unwrap_or_default();
+        let sessions = SessionList::new(Some(salvage(&String::from_utf8_lossy(&contents))));
+        let recovered = sessions.iter().count();
+        let name = broken
+            .path()
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+
+        match broken.quarantine() {
+            Ok(path) => {
+                eprintln!(
+                    "Could not read {name}: {reason}. It was moved to {}, and {recovered} sessions were recovered from it.\n",
+                    path.display()
+                );
+                if let Err(e) = storage.write(sessions.to_jsonl()) {
+                    eprintln!("Could not write the recovered sessions: {e}");
+                }
+            }
+            Err(e) => eprintln!(
+                "Could not read {name}: {reason}, nor move it out of the way: {e}. {recovered} sessions were recovered from it for now.\n"
+            ),
+        }
+
+        sessions
+    }
+}
+
+/// Finds the sessions which can still be read in a broken `sessions.jsonl`,
+/// keeping every line which is a session. A line which is not may be a
+/// session which was cut off, followed by one which was appended after it,
+/// so the sessions in it are looked for with `salvage_objects`.
+fn salvage_lines(contents: &str) -> Vec<Session> {
+    contents
+        .lines()
+        .flat_map(|line| match Session::from_json(line) {
+            Some(session) => vec![session],
+            None => salvage_objects(line),
+        })
+        .collect()
+}
+
+/// Finds the sessions which can still be read in a broken `sessions.json`
+/// of older versions. A session is a JSON object without objects in it, so
+/// sessions are found even if the JSON around them is broken. Sessions
+/// with a brace in their note or project are lost.
+fn salvage_objects(contents: &str) -> Vec<Session> {
+    let object = Regex::new(r"\{[^{}]*\}").unwrap();
+
+    object
+        .find_iter(contents)
+        .filter_map(|object| {
+            // Sessions of older versions may be stored in minutes.
+            let list = format!("{{\"sessions\":[{}]}}", object.as_str());
+            SessionList::from_json(&migrate_sessions(&list))?
+                .sessions
+                .pop()
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -125,13 +541,18 @@ mod tests {
     fn test_session_new_creates_new_session() {
         let session1: Session = Session::new(
             Some(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).single().unwrap()),
-            25,
-            5,
+            25 * 60,
+            5 * 60,
         );
         let session2: Session = Session {
             timestamp: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).single().unwrap(),
-            work_time: 25,
-            break_time: 5,
+            work_seconds: 25 * 60,
+            break_seconds: 5 * 60,
+            paused_seconds: 0,
+            status: SessionStatus::Completed,
+            project: None,
+            tags: Vec::new(),
+            note: None,
         };
 
         assert_eq!(session1, session2);
@@ -139,11 +560,16 @@ mod tests {
 
     #[test]
     fn test_session_new_create_new_session_with_none() {
-        let session1: Session = Session::new(None, 25, 5);
+        let session1: Session = Session::new(None, 25 * 60, 5 * 60);
         let session2: Session = Session {
             timestamp: Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 0).single().unwrap(),
-            work_time: 25,
-            break_time: 5,
+            work_seconds: 25 * 60,
+            break_seconds: 5 * 60,
+            paused_seconds: 0,
+            status: SessionStatus::Completed,
+            project: None,
+            tags: Vec::new(),
+            note: None,
         };
 
         assert_eq!(session1, session2);
@@ -151,11 +577,16 @@ mod tests {
 
     #[test]
     fn test_session_new_not_equal() {
-        let session1: Session = Session::new(None, 25, 5);
+        let session1: Session = Session::new(None, 25 * 60, 5 * 60);
         let session2: Session = Session {
             timestamp: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).single().unwrap(),
-            work_time: 25,
-            break_time: 5,
+            work_seconds: 25 * 60,
+            break_seconds: 5 * 60,
+            paused_seconds: 0,
+            status: SessionStatus::Completed,
+            project: None,
+            tags: Vec::new(),
+            note: None,
         };
 
         assert_ne!(session1, session2);
@@ -163,15 +594,70 @@ mod tests {
 
     #[test]
     fn test_session_clone_is_equal() {
-        let session1 = Session::new(None, 5, 5);
+        let session1 = Session::new(None, 5 * 60, 5 * 60);
         assert_eq!(session1, session1.clone());
     }
 
+    #[test]
+    fn test_session_from_json_without_paused_seconds() {
+        let json = "{\"timestamp\":1735689600,\"work_seconds\":1500,\"break_seconds\":300}";
+        let session = Session::from_json(json).expect("Invalid JSON");
+
+        assert_eq!(session.paused_seconds, 0);
+        assert_eq!(
+            session,
+            Session::new(
+                Some(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).single().unwrap()),
+                25 * 60,
+                5 * 60
+            )
+        );
+    }
+
+    #[test]
+    fn test_session_status_to_json_and_back() {
+        let session = Session {
+            status: SessionStatus::SkippedBreak,
+            ..Session::new(None, 25 * 60, 0)
+        };
+        let json = session.to_json();
+
+        assert!(json.contains("\"status\":\"skipped-break\""));
+        assert_eq!(Session::from_json(&json), Some(session));
+    }
+
+    #[test]
+    fn test_session_without_status_is_completed() {
+        let json = "{\"timestamp\":0,\"work_seconds\":1500,\"break_seconds\":300}";
+        assert_eq!(
+            Session::from_json(json).unwrap().status,
+            SessionStatus::Completed
+        );
+    }
+
+    #[test]
+    fn test_sessionlist_last_mut_updates_last_session() {
+        let mut session_list = SessionList::new(None);
+        assert!(session_list.last_mut().is_none());
+
+        session_list.append(Session::new(None, 25 * 60, 0));
+        session_list.append(Session::new(None, 50 * 60, 0));
+        session_list.last_mut().unwrap().break_seconds = 10 * 60;
+
+        assert_eq!(
+            session_list,
+            SessionList::new(Some(vec![
+                Session::new(None, 25 * 60, 0),
+                Session::new(None, 50 * 60, 10 * 60),
+            ]))
+        );
+    }
+
     #[test]
     fn test_sessionlist_new_creates_new_session() {
-        let session1 = Session::new(None, 25, 5);
-        let session2 = Session::new(None, 40, 5);
-        let session3 = Session::new(None, 10, 10);
+        let session1 = Session::new(None, 25 * 60, 5 * 60);
+        let session2 = Session::new(None, 40 * 60, 5 * 60);
+        let session3 = Session::new(None, 10 * 60, 10 * 60);
         let sessions_new: SessionList = SessionList::new(Some(vec![
             session1.clone(),
             session2.clone(),
@@ -196,7 +682,7 @@ mod tests {
     #[test]
     fn test_sessionlist_not_equal() {
         // TODO: get some better naming
-        let session1 = Session::new(None, 25, 5);
+        let session1 = Session::new(None, 25 * 60, 5 * 60);
         let sessions1 = SessionList::new(None);
         let sessions2 = SessionList::new(Some(vec![session1]));
 
@@ -205,8 +691,8 @@ mod tests {
 
     #[test]
     fn test_sessionlist_append_does_append() {
-        let session = Session::new(None, 25, 5);
-        let appended_session = Session::new(None, 5, 5);
+        let session = Session::new(None, 25 * 60, 5 * 60);
+        let appended_session = Session::new(None, 5 * 60, 5 * 60);
 
         let mut sessions_append = SessionList::new(Some(vec![session.clone()]));
         sessions_append.append(appended_session.clone());
@@ -217,37 +703,251 @@ mod tests {
     }
 
     #[test]
-    fn test_sessionlist_get_total_work_minutes() {
+    fn test_sessionlist_get_total_work_seconds() {
         let session_list = SessionList::new(Some(vec![
-            Session::new(None, 25, 5),
-            Session::new(None, 35, 5),
-            Session::new(None, 100, 0),
+            Session::new(None, 25 * 60, 5 * 60),
+            Session::new(None, 35 * 60, 5 * 60),
+            Session::new(None, 100 * 60, 0),
         ]));
 
-        assert_eq!(session_list.total_work_minutes(), 160);
+        assert_eq!(session_list.total_work_seconds(), 160 * 60);
+    }
+
+    #[test]
+    fn test_sessionlist_pomodoros_on_counts_local_date() {
+        let at = |day, hour| {
+            Local
+                .with_ymd_and_hms(2025, 1, day, hour, 0, 0)
+                .single()
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let session_list = SessionList::new(Some(vec![
+            Session::new(Some(at(1, 9)), 25 * 60, 5 * 60),
+            Session::new(Some(at(2, 0)), 25 * 60, 5 * 60),
+            Session::new(Some(at(2, 12)), 25 * 60, 5 * 60),
+            Session::new(Some(at(2, 23)), 25 * 60, 15 * 60),
+            Session {
+                status: SessionStatus::Aborted,
+                ..Session::new(Some(at(2, 23)), 10 * 60, 0)
+            },
+        ]));
+
+        let day = |day| NaiveDate::from_ymd_opt(2025, 1, day).unwrap();
+        assert_eq!(session_list.pomodoros_on(day(1)), 1);
+        assert_eq!(session_list.pomodoros_on(day(2)), 3);
+        assert_eq!(session_list.pomodoros_on(day(3)), 0);
+    }
+
+    #[test]
+    fn test_session_without_labels_has_none() {
+        let json = "{\"timestamp\":0,\"work_seconds\":1500,\"break_seconds\":300}";
+        let session = Session::from_json(json).unwrap();
+
+        assert_eq!(session.project, None);
+        assert!(session.tags.is_empty());
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(
+            parse_tags("deep, writing,deep  review"),
+            vec!["deep", "writing", "review"]
+        );
+        assert!(parse_tags(" , ").is_empty());
+    }
+
+    #[test]
+    fn test_sessionlist_work_seconds_by_project_and_tag() {
+        let tags = |tags: &str| parse_tags(tags);
+        let session_list = SessionList::new(Some(vec![
+            Session::new(None, 25 * 60, 0).with_labels(Some("acme".to_string()), tags("deep")),
+            Session::new(None, 50 * 60, 0)
+                .with_labels(Some("acme".to_string()), tags("deep,review")),
+            Session::new(None, 10 * 60, 0).with_labels(Some("home".to_string()), Vec::new()),
+            Session::new(None, 90 * 60, 0),
+        ]));
+
+        assert_eq!(
+            session_list.work_seconds_by_project(),
+            BTreeMap::from([("acme".to_string(), 75 * 60), ("home".to_string(), 10 * 60)])
+        );
+        assert_eq!(
+            session_list.work_seconds_by_tag(),
+            BTreeMap::from([
+                ("deep".to_string(), 75 * 60),
+                ("review".to_string(), 50 * 60)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_sessionlist_search_notes_projects_and_tags() {
+        let noted = |note: &str| Session {
+            note: Some(note.to_string()),
+            ..Session::new(None, 25 * 60, 5 * 60)
+        };
+        let session_list = SessionList::new(Some(vec![
+            noted("Fixed the Parser"),
+            Session::new(None, 25 * 60, 0).with_labels(Some("parser".to_string()), Vec::new()),
+            Session::new(None, 25 * 60, 0).with_labels(None, parse_tags("review")),
+            noted("wrote docs"),
+            Session::new(None, 25 * 60, 0),
+        ]));
+
+        let found = session_list.search("PARSER");
+        assert_eq!(found.len(), 2);
+        // The newest session comes first.
+        assert_eq!(found[0].project, Some("parser".to_string()));
+        assert_eq!(found[1].note, Some("Fixed the Parser".to_string()));
+
+        assert_eq!(session_list.search("review").len(), 1);
+        assert_eq!(session_list.search("nothing").len(), 0);
+        assert_eq!(session_list.search("").len(), 5);
+    }
+
+    #[test]
+    fn test_sessionlist_merge_skips_recorded_sessions() {
+        let at = |hour| {
+            Utc.with_ymd_and_hms(2025, 1, 1, hour, 0, 0)
+                .single()
+                .unwrap()
+        };
+        let mut session_list = SessionList::new(Some(vec![
+            Session::new(Some(at(9)), 25 * 60, 5 * 60),
+            Session::new(Some(at(11)), 25 * 60, 5 * 60),
+        ]));
+
+        let imported = vec![
+            Session::new(Some(at(10)), 25 * 60, 5 * 60),
+            // Already recorded, even though the note differs.
+            Session {
+                note: Some("Imported".to_string()),
+                ..Session::new(Some(at(9)), 25 * 60, 5 * 60)
+            },
+        ];
+        assert_eq!(session_list.merge(imported.clone()), 1);
+        assert_eq!(session_list.merge(imported), 0);
+
+        assert_eq!(session_list.total_work_seconds(), 75 * 60);
+        let hours: Vec<DateTime<Utc>> = session_list
+            .iter()
+            .map(|session| session.timestamp)
+            .collect();
+        assert_eq!(hours, vec![at(9), at(10), at(11)]);
+    }
+
+    #[test]
+    fn test_sessionlist_id_of_ignores_changes_to_the_break() {
+        let at = |hour| Utc.with_ymd_and_hms(2025, 1, 13, hour, 0, 0).unwrap();
+        let list = SessionList::new(Some(vec![
+            Session::new(Some(at(9)), 25 * 60, 0),
+            Session::new(Some(at(10)), 25 * 60, 0),
+        ]));
+
+        assert_eq!(
+            list.id_of(&Session::new(Some(at(10)), 25 * 60, 5 * 60)),
+            Some(2)
+        );
+        assert_eq!(list.id_of(&Session::new(Some(at(10)), 20 * 60, 0)), None);
+    }
+
+    #[test]
+    fn test_session_new_stores_whole_seconds() {
+        let at = Utc.with_ymd_and_hms(2025, 1, 13, 9, 0, 0).unwrap();
+        let session = Session::new(Some(at + chrono::TimeDelta::milliseconds(750)), 25 * 60, 0);
+
+        assert_eq!(session.timestamp, at);
+        assert_eq!(Session::from_json(&session.to_json()), Some(session));
+    }
+
+    #[test]
+    fn test_sessionlist_insert_keeps_the_order() {
+        let at = |hour| Utc.with_ymd_and_hms(2025, 1, 13, hour, 0, 0).unwrap();
+        let mut list = SessionList::new(Some(vec![
+            Session::new(Some(at(9)), 25 * 60, 0),
+            Session::new(Some(at(11)), 25 * 60, 0),
+        ]));
+
+        assert_eq!(list.insert(Session::new(Some(at(10)), 50 * 60, 0)), 2);
+        assert_eq!(list.insert(Session::new(Some(at(12)), 10 * 60, 0)), 4);
+        assert_eq!(list.get(2).unwrap().work_seconds, 50 * 60);
+        assert_eq!(list.get(4).unwrap().work_seconds, 10 * 60);
+    }
+
+    #[test]
+    fn test_sessionlist_get_and_remove_by_id() {
+        let mut session_list = SessionList::new(Some(vec![
+            Session::new(None, 10 * 60, 0),
+            Session::new(None, 20 * 60, 0),
+            Session::new(None, 30 * 60, 0),
+        ]));
+
+        assert_eq!(session_list.get(0), None);
+        assert_eq!(session_list.get(1).unwrap().work_seconds, 10 * 60);
+        assert_eq!(session_list.get(4), None);
+
+        session_list.get_mut(3).unwrap().note = Some("Edited".to_string());
+        assert_eq!(
+            session_list.get(3).unwrap().note,
+            Some("Edited".to_string())
+        );
+
+        assert_eq!(session_list.remove(0), None);
+        assert_eq!(session_list.remove(4), None);
+        assert_eq!(session_list.remove(2).unwrap().work_seconds, 20 * 60);
+
+        let ids: Vec<(usize, u64)> = session_list
+            .entries()
+            .map(|(id, session)| (id, session.work_seconds))
+            .collect();
+        assert_eq!(ids, vec![(1, 10 * 60), (2, 30 * 60)]);
+    }
+
+    #[test]
+    fn test_sessionlist_jsonl_round_trip() {
+        let sessions = SessionList::new(Some(vec![
+            Session::new(None, 25 * 60, 5 * 60),
+            Session::new(None, 10 * 60, 0).with_labels(Some("acme".to_string()), Vec::new()),
+        ]));
+        let jsonl = sessions.to_jsonl();
+
+        assert_eq!(jsonl.lines().count(), 2);
+        assert_eq!(
+            SessionList::from_jsonl(format!("{jsonl}\n").as_bytes()),
+            Ok(sessions)
+        );
+    }
+
+    #[test]
+    fn test_sessionlist_from_jsonl_reports_the_line() {
+        let jsonl = "{\"timestamp\":1735689600,\"work_seconds\":1500,\"break_seconds\":300}\n\
+                     {\"timestamp\":17356";
+
+        assert_eq!(
+            SessionList::from_jsonl(jsonl.as_bytes()),
+            Err("Could not read line 2 of the sessions".to_string())
+        );
     }
 
     #[test]
     fn test_load_sessions() {
         // Write some sessions to a file
-        let storage = Storage::new(
-            Some(".tomato_test".to_string()),
-            "sessions.json".to_string(),
-        );
+        let storage = Storage::new(Some(".tomato_test".to_string()), SESSIONS_FILE.to_string());
         let sessions = SessionList::new(Some(vec![
-            Session::new(None, 25, 5),
-            Session::new(None, 10, 5),
+            Session::new(None, 25 * 60, 5 * 60),
+            Session::new(None, 10 * 60, 5 * 60),
             Session::new(
                 Some(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).single().unwrap()),
-                1,
+                90,
                 1,
             ),
         ]));
 
-        let _ = storage.write(sessions.to_json());
+        let _ = storage.write(sessions.to_jsonl());
 
         assert_eq!(
-            SessionList::load_sessions(".tomato_test".to_string(), "sessions.json".to_string()),
+            SessionList::load_sessions(".tomato_test".to_string()),
             sessions
         );
 
@@ -258,14 +958,119 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_load_sessions_migrates_the_json_array_in_minutes() {
+        let folder = ".tomato_test_minutes".to_string();
+        let legacy = Storage::new(Some(folder.clone()), LEGACY_SESSIONS_FILE.to_string());
+        let _ = legacy.write(
+            "{\"sessions\":[{\"timestamp\":1735689600,\"work_time\":25,\"break_time\":5}]}"
+                .to_string(),
+        );
+        let expected = SessionList::new(Some(vec![Session::new(
+            Some(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).single().unwrap()),
+            25 * 60,
+            5 * 60,
+        )]));
+
+        assert_eq!(SessionList::load_sessions(folder.clone()), expected);
+
+        // The sessions were moved to `sessions.jsonl`, and the old file kept.
+        let storage = Storage::new(Some(folder.clone()), SESSIONS_FILE.to_string());
+        assert_eq!(storage.read().unwrap(), expected.to_jsonl());
+        assert!(legacy.read().is_err());
+        assert!(legacy.path().with_extension("json.bak").exists());
+        assert_eq!(SessionList::load_sessions(folder), expected);
+
+        let _ = remove_dir_all(format!(
+            "{}/{}",
+            storage::get_home_path_with(home_dir),
+            ".tomato_test_minutes"
+        ));
+    }
+
+    #[test]
+    fn test_salvage_lines_keeps_braces_in_notes() {
+        let braced = Session {
+            note: Some("Refactored {parser} and }{ more".to_string()),
+            ..Session::new(
+                Some(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()),
+                1500,
+                300,
+            )
+        };
+        let appended = Session::new(
+            Some(Utc.with_ymd_and_hms(2025, 1, 2, 0, 0, 0).unwrap()),
+            600,
+            0,
+        );
+        // A session which was cut off, with the next one appended to it.
+        let contents = format!(
+            "{}\n{{\"timestamp\":17356{}\n\n{{\"note\":\"not a session\"}}\n",
+            braced.to_json(),
+            appended.to_json()
+        );
+
+        assert_eq!(salvage_lines(&contents), vec![braced, appended]);
+    }
+
+    #[test]
+    fn test_salvage_objects() {
+        let contents = "{\"sessions\":[{\"timestamp\":1735689600,\"work_seconds\":1500,\"break_seconds\":300},\
+                        {\"timestamp\":1735693200,\"work_sec\
+                        {\"timestamp\":17356{\"timestamp\":1735696800,\"work_time\":25,\"break_time\":5},\
+                        {\"note\":\"not a session\"}";
+        let salvaged = salvage_objects(contents);
+
+        assert_eq!(salvaged.len(), 2);
+        assert_eq!(salvaged[0].work_seconds, 25 * 60);
+        assert_eq!(salvaged[1].timestamp.timestamp(), 1735696800);
+        assert_eq!(salvaged[1].break_seconds, 5 * 60);
+    }
+
+    #[test]
+    fn test_load_sessions_recovers_a_broken_file() {
+        let folder = ".tomato_test_broken".to_string();
+        let storage = Storage::new(Some(folder.clone()), SESSIONS_FILE.to_string());
+        let session = Session::new(
+            Some(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).single().unwrap()),
+            25 * 60,
+            5 * 60,
+        );
+        let _ = storage.write(format!("{}\n{{\"timestamp\":17", session.to_json()));
+
+        let expected = SessionList::new(Some(vec![session]));
+        assert_eq!(SessionList::load_sessions(folder.clone()), expected);
+
+        // The salvaged sessions replace the broken file, which is kept.
+        assert_eq!(storage.read().unwrap(), expected.to_jsonl());
+        let folder_path = format!("{}/{}", storage::get_home_path_with(home_dir), folder);
+        let quarantined = fs::read_dir(&folder_path)
+            .unwrap()
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with("sessions.jsonl.corrupt-")
+            })
+            .count();
+        assert_eq!(quarantined, 1);
+
+        let _ = remove_dir_all(folder_path);
+    }
+
     #[test]
     fn test_load_sessions_no_file() {
         assert_eq!(
-            SessionList::load_sessions(
-                ".tomato_test".to_string(),
-                "sessions_non_existant.json".to_string()
-            ),
+            SessionList::load_sessions(".tomato_test_non_existant".to_string()),
             SessionList::new(None)
         );
+        // Loading creates nothing, not even the folder.
+        assert!(!Path::new(&format!(
+            "{}/{}",
+            storage::get_home_path_with(home_dir),
+            ".tomato_test_non_existant"
+        ))
+        .exists());
     }
 }
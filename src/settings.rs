@@ -1,8 +1,26 @@
 use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
 
-use crate::{json_serializable::JsonSerializable, storage::Storage};
+use crate::{
+    json_serializable::JsonSerializable,
+    migration::{is_correct_version, migrate_settings},
+    storage::Storage,
+};
 
-pub const SETTINGS_VERSION: &str = "0.2";
+pub const SETTINGS_VERSION: &str = "0.6";
+
+/// The length of a long break in minutes, if nothing else is set.
+pub const DEFAULT_LONG_BREAK_TIME: u64 = 15;
+
+/// The amount of pomodoros between each long break, if nothing else is set.
+pub const DEFAULT_LONG_BREAK_INTERVAL: u64 = 4;
+
+/// The amount of pomodoros to do each day, if nothing else is set.
+pub const DEFAULT_DAILY_GOAL: u64 = 8;
+
+/// The highest daily goal which can be set, a pomodoro every half hour of
+/// the day.
+pub const MAX_DAILY_GOAL: u64 = 48;
 
 /// The `Settings` struct holds all the settings which will be saved and loaded
 /// from a file.
@@ -17,7 +35,17 @@ pub struct Settings {
     pub version: String,
     pub work_time: u64,
     pub break_time: u64,
+    pub long_break_time: u64,
+    // A long break is taken instead of a short break after every
+    // `long_break_interval` pomodoros. 0 disables long breaks.
+    pub long_break_interval: u64,
     pub notification: Notifications,
+    pub hooks: Hooks,
+    // Asks what was accomplished after each pomodoro, and stores the answer
+    // as the note of the session.
+    pub prompt_for_note: bool,
+    // The amount of pomodoros to do each day. 0 disables the goal.
+    pub daily_goal: u64,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -37,6 +65,38 @@ impl Default for Notifications {
     }
 }
 
+/// Shell commands which are run when the timer moves between phases, e.g.
+/// to mute a chat application while working.
+///
+/// The commands are run with `sh -c`, with environment variables describing
+/// the session. See `hooks.rs`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct Hooks {
+    pub work_start: Option<String>,
+    pub work_end: Option<String>,
+    pub break_start: Option<String>,
+    pub break_end: Option<String>,
+    pub pause: Option<String>,
+    pub abort: Option<String>,
+    // Commands which run for longer than this are killed.
+    pub timeout_seconds: u64,
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        Hooks {
+            work_start: None,
+            work_end: None,
+            break_start: None,
+            break_end: None,
+            pause: None,
+            abort: None,
+            timeout_seconds: 10,
+        }
+    }
+}
+
 impl JsonSerializable for Settings {}
 
 impl Settings {
@@ -49,48 +109,95 @@ impl Settings {
     /// ## Returns
     /// A new `Settings` instance where the version of the settings, is the one
     /// which is set in the `SETTINGS_VERSION` const. As well as the break
-    /// and work time specified in the arguments.
+    /// and work time specified in the arguments. Long breaks use the default
+    /// length and interval, as does the daily goal. No hooks are set, and no
+    /// notes are asked for.
     pub fn new(work_time: u64, break_time: u64, notification: Notifications) -> Self {
         Self {
             version: SETTINGS_VERSION.to_string(),
             work_time,
             break_time,
+            long_break_time: DEFAULT_LONG_BREAK_TIME,
+            long_break_interval: DEFAULT_LONG_BREAK_INTERVAL,
             notification,
+            hooks: Hooks::default(),
+            prompt_for_note: false,
+            daily_goal: DEFAULT_DAILY_GOAL,
         }
     }
 
     /// Finds the settings from `settings.json` and deserializes into the
     /// `Setting` struct.
     ///
+    /// Settings from older versions are migrated to the current version.
+    ///
     /// ## Returns
     /// * A Setting struct containing all previous sessions stored in
     ///   `settings.json`.
     pub fn load_settings(folder: String, file_name: String) -> Settings {
         let storage = Storage::new(Some(folder), file_name.clone());
 
-        let contents = storage.read().unwrap_or_else(|_| {
-            let settings = Settings::new(25, 5, Notifications::default());
-            match storage.write(settings.to_json()) {
-                Ok(_) => (),
-                Err(v) => panic!(
-                    "An error occured while writing the settings to the settings file: {}",
-                    v
-                ),
+        let contents = match storage.read() {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let settings = Settings::new(25, 5, Notifications::default());
+                if let Err(e) = storage.write(settings.to_json()) {
+                    eprintln!("Could not write the default settings: {e}");
+                }
+                "{}".to_string()
             }
-            "{}".to_string()
-        });
+            // E.g. the file is not valid UTF-8.
+            Err(e) => return Settings::recover(&storage, &file_name, &e.to_string()),
+        };
 
-        if contents.is_empty() || contents == "{}" {
-            Settings::new(25, 5, Notifications::default())
+        let loaded = if contents.is_empty() || contents == "{}" {
+            Ok(Settings::new(25, 5, Notifications::default()))
+        } else if !is_correct_version(&contents, SETTINGS_VERSION) {
+            migrate_settings(&contents, &storage)
         } else {
-            Settings::from_json(&contents).expect("Could not parse the contents of file.")
+            Settings::from_json(&contents).ok_or_else(|| "the settings are not valid".to_string())
+        };
+
+        loaded.unwrap_or_else(|reason| Settings::recover(&storage, &file_name, &reason))
+    }
+
+    /// Moves a settings file which could not be read out of the way, tells
+    /// the user, and writes the default settings in its place.
+    ///
+    /// ## Arguments
+    /// * reason: Why the settings could not be read.
+    ///
+    /// ## Returns
+    /// The default settings.
+    fn recover(storage: &Storage, file_name: &str, reason: &str) -> Settings {
+        let settings = Settings::new(25, 5, Notifications::default());
+
+        match storage.quarantine() {
+            Ok(path) => {
+                eprintln!(
+                    "Could not read {file_name}: {reason}. It was moved to {}, and the default settings are used instead.\n",
+                    path.display()
+                );
+                if let Err(e) = storage.write(settings.to_json()) {
+                    eprintln!("Could not write the default settings: {e}");
+                }
+            }
+            Err(e) => eprintln!(
+                "Could not read {file_name}: {reason}, nor move it out of the way: {e}. The default settings are used for now.\n"
+            ),
         }
+
+        settings
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use home::home_dir;
+    use std::fs;
+
     use super::*;
+    use crate::storage;
 
     #[test]
     fn serialize_settings_to_json_and_back() {
@@ -103,4 +210,33 @@ mod tests {
 
         assert_eq!(settings, deserialized_settings);
     }
+
+    #[test]
+    fn test_load_settings_recovers_a_broken_file() {
+        let folder = ".tomato_test_broken_settings".to_string();
+        let storage = Storage::new(Some(folder.clone()), "settings.json".to_string());
+        let _ = storage.write(format!("{{\"version\":\"{SETTINGS_VERSION}\",\"work_ti"));
+
+        let defaults = Settings::new(25, 5, Notifications::default());
+        assert_eq!(
+            Settings::load_settings(folder.clone(), "settings.json".to_string()),
+            defaults
+        );
+        // The defaults are written in place of the broken file, which is kept.
+        assert_eq!(storage.read().unwrap(), defaults.to_json());
+
+        let folder_path = format!("{}/{}", storage::get_home_path_with(home_dir), folder);
+        let quarantined = fs::read_dir(&folder_path)
+            .unwrap()
+            .filter_map(Result::ok)
+            .any(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with("settings.json.corrupt-")
+            });
+        assert!(quarantined);
+
+        let _ = fs::remove_dir_all(folder_path);
+    }
 }
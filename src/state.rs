@@ -0,0 +1,195 @@
+//! # State
+//! This file contains the state of the timer which is currently running.
+//!
+//! The state is written to `current.json` when a timer starts and at
+//! intervals while it runs, so that a timer which was interrupted by a crash
+//! or a closed terminal can be resumed, or recorded as a partial session.
+
+use chrono::serde::{ts_seconds, ts_seconds_option};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::daemon;
+use crate::json_serializable::JsonSerializable;
+use crate::storage::Storage;
+
+/// The name of the file holding the state of the running timer.
+const STATE_FILE: &str = "current.json";
+
+/// The phases a running timer can be in.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Work,
+    Break,
+}
+
+impl Phase {
+    /// The name of the phase, as shown to the user.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Work => "work",
+            Phase::Break => "break",
+        }
+    }
+}
+
+/// Holds the state of the timer which is currently running.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CurrentState {
+    pub phase: Phase,
+    // The length of the current phase.
+    pub duration_seconds: u64,
+    // How much of the current phase had been counted down at `updated_at`.
+    pub elapsed_seconds: u64,
+    pub paused: bool,
+    // The length of the work phase of this cycle. Needed to record the
+    // session, once the break is over.
+    pub work_seconds: u64,
+    // The time this cycle has been paused for, in all phases so far.
+    pub paused_seconds: u64,
+    #[serde(with = "ts_seconds")]
+    pub updated_at: DateTime<Utc>,
+    // The project and tags the session is recorded with.
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // The time the work of this cycle was recorded at, which finds its
+    // session once the break is over.
+    #[serde(default, with = "ts_seconds_option")]
+    pub work_recorded_at: Option<DateTime<Utc>>,
+}
+
+impl JsonSerializable for CurrentState {}
+
+impl CurrentState {
+    /// Creates the state of a phase which is just starting.
+    ///
+    /// ## Arguments
+    /// * phase: The phase which is starting.
+    /// * duration_seconds: The length of the phase.
+    /// * work_seconds: The length of the work phase of this cycle.
+    /// * paused_seconds: The time the cycle has been paused for before this
+    ///   phase.
+    pub fn new(
+        phase: Phase,
+        duration_seconds: u64,
+        work_seconds: u64,
+        paused_seconds: u64,
+    ) -> CurrentState {
+        CurrentState {
+            phase,
+            duration_seconds,
+            elapsed_seconds: 0,
+            paused: false,
+            work_seconds,
+            paused_seconds,
+            updated_at: Utc::now(),
+            project: None,
+            tags: Vec::new(),
+            work_recorded_at: None,
+        }
+    }
+
+    /// Gets the time which is left of the phase, as of the last update.
+    pub fn remaining_seconds(&self) -> u64 {
+        self.duration_seconds.saturating_sub(self.elapsed_seconds)
+    }
+
+    /// Gets the time which is left of the phase at `now`. Unless the timer
+    /// is paused, it has kept counting down since the last update.
+    pub fn remaining_at(&self, now: DateTime<Utc>) -> u64 {
+        if self.paused {
+            return self.remaining_seconds();
+        }
+
+        let since_update = (now - self.updated_at).num_seconds().max(0) as u64;
+        self.remaining_seconds().saturating_sub(since_update)
+    }
+
+    /// Finds the state of the running timer in `current.json`.
+    ///
+    /// ## Returns
+    /// The state, or `None` if no timer is running or the file could not be
+    /// parsed.
+    pub fn load() -> Option<CurrentState> {
+        let contents = Storage::new(None, STATE_FILE.to_string()).read().ok()?;
+        CurrentState::from_json(&contents)
+    }
+
+    /// Finds a timer which was interrupted and can be resumed. While the
+    /// daemon runs, `current.json` holds the state of its timer instead.
+    ///
+    /// ## Returns
+    /// The state, or `None` if there is no such timer.
+    pub fn unfinished() -> Option<CurrentState> {
+        if daemon::is_running() {
+            return None;
+        }
+        CurrentState::load()
+    }
+
+    /// Writes the state to `current.json`. It is written every few seconds,
+    /// so no backup of it is kept.
+    pub fn save(&self) -> std::io::Result<()> {
+        Storage::new(None, STATE_FILE.to_string()).write_scratch(self.to_json())
+    }
+
+    /// Removes `current.json`, as no timer is running anymore.
+    pub fn clear() {
+        let storage = Storage::new(None, STATE_FILE.to_string());
+        // There is nothing to clear if the file does not exist. Earlier
+        // versions kept a backup of the state, which is removed as well.
+        let _ = storage.remove();
+        let _ = fs::remove_file(storage.backup_path());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current_state_new_starts_at_zero() {
+        let state = CurrentState::new(Phase::Break, 300, 1500, 20);
+
+        assert_eq!(state.elapsed_seconds, 0);
+        assert_eq!(state.remaining_seconds(), 300);
+        assert_eq!(state.paused_seconds, 20);
+        assert!(!state.paused);
+    }
+
+    #[test]
+    fn test_current_state_remaining_never_underflows() {
+        let mut state = CurrentState::new(Phase::Work, 60, 60, 0);
+        state.elapsed_seconds = 90;
+
+        assert_eq!(state.remaining_seconds(), 0);
+    }
+
+    #[test]
+    fn test_current_state_remaining_at_counts_down_unless_paused() {
+        let mut state = CurrentState::new(Phase::Work, 60, 60, 0);
+        state.elapsed_seconds = 10;
+        let later = state.updated_at + chrono::TimeDelta::seconds(20);
+
+        assert_eq!(state.remaining_at(later), 30);
+        assert_eq!(state.remaining_at(later + chrono::TimeDelta::minutes(5)), 0);
+
+        state.paused = true;
+        assert_eq!(state.remaining_at(later), 50);
+    }
+
+    #[test]
+    fn test_current_state_to_json_and_back() {
+        let mut state = CurrentState::new(Phase::Work, 1500, 1500, 0);
+        // Only whole seconds are stored.
+        state.updated_at = DateTime::from_timestamp(1735689600, 0).unwrap();
+        let json = state.to_json();
+
+        assert!(json.contains("\"phase\":\"work\""));
+        assert_eq!(CurrentState::from_json(&json), Some(state));
+    }
+}
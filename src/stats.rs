@@ -0,0 +1,973 @@
+//! # Stats
+//! This file contains the statistics of the sessions, as shown by
+//! `tomato stats`, such as the breakdown per calendar period, streaks, the
+//! heatmap and the histograms.
+//!
+//! Sessions are stored with UTC timestamps, but are grouped by the local
+//! calendar, so that a pomodoro late in the evening counts towards the day
+//! it was done on.
+
+use chrono::{Datelike, Local, NaiveDate, TimeDelta, Timelike};
+use clap::ValueEnum;
+use crossterm::style::{Color, Stylize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use crate::duration::{format_duration, seconds_before};
+use crate::render::Render;
+use crate::session::{Session, SessionList, SessionStatus};
+use crate::settings::Settings;
+
+/// The calendar periods sessions can be grouped by.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Period {
+    Day,
+    // Weeks start on Monday.
+    Week,
+    Month,
+    Year,
+}
+
+impl Period {
+    /// Finds the first day of the period `date` is in.
+    pub fn start_of(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => date,
+            Period::Week => date - TimeDelta::days(date.weekday().num_days_from_monday() as i64),
+            Period::Month => date.with_day(1).unwrap(),
+            Period::Year => date.with_ordinal(1).unwrap(),
+        }
+    }
+
+    /// Names the period which starts on `start`, e.g. `2025-01-31`,
+    /// `2025-W05`, `2025-01` or `2025`.
+    pub fn label(&self, start: NaiveDate) -> String {
+        match self {
+            Period::Day => start.format("%Y-%m-%d").to_string(),
+            Period::Week => {
+                let week = start.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Period::Month => start.format("%Y-%m").to_string(),
+            Period::Year => start.format("%Y").to_string(),
+        }
+    }
+
+    /// The name of the period, as a column heading.
+    fn heading(&self) -> &'static str {
+        match self {
+            Period::Day => "Day",
+            Period::Week => "Week",
+            Period::Month => "Month",
+            Period::Year => "Year",
+        }
+    }
+}
+
+/// The sessions of one calendar period, added up.
+#[derive(Debug, PartialEq)]
+pub struct Bucket {
+    // The first day of the period.
+    pub start: NaiveDate,
+    // Pomodoros whose work was not aborted.
+    pub pomodoros: u64,
+    pub work_seconds: u64,
+    pub break_seconds: u64,
+}
+
+impl Bucket {
+    fn new(start: NaiveDate) -> Bucket {
+        Bucket {
+            start,
+            pomodoros: 0,
+            work_seconds: 0,
+            break_seconds: 0,
+        }
+    }
+
+    fn add(&mut self, session: &Session) {
+        if session.status != SessionStatus::Aborted {
+            self.pomodoros += 1;
+        }
+        self.work_seconds += session.work_seconds;
+        self.break_seconds += session.break_seconds;
+    }
+}
+
+/// Groups the sessions by the local calendar period they were done in.
+///
+/// ## Returns
+/// A bucket for each period with sessions in it, oldest first.
+pub fn group_by_period(sessions: &SessionList, period: Period) -> Vec<Bucket> {
+    let mut buckets: Vec<Bucket> = Vec::new();
+
+    for session in sessions.iter() {
+        let date = session.timestamp.with_timezone(&Local).date_naive();
+        let start = period.start_of(date);
+
+        // Sessions are mostly in order, so the bucket is usually the last.
+        match buckets
+            .iter_mut()
+            .rev()
+            .find(|bucket| bucket.start == start)
+        {
+            Some(bucket) => bucket.add(session),
+            None => {
+                let mut bucket = Bucket::new(start);
+                bucket.add(session);
+                buckets.push(bucket);
+            }
+        }
+    }
+
+    buckets.sort_by_key(|bucket| bucket.start);
+    buckets
+}
+
+/// The runs of consecutive days on which the daily goal was met.
+#[derive(Debug, PartialEq)]
+pub struct Streaks {
+    // Days in a row up to today. Today only breaks the streak once it is
+    // over, so it counts if the goal is met, but is skipped if not yet.
+    pub current: u64,
+    pub longest: u64,
+}
+
+/// Finds the current and the longest streak of days on which at least `goal`
+/// pomodoros were done.
+///
+/// ## Arguments
+/// * goal: The daily goal. With a goal of 0 there are no streaks.
+/// * today: The local date of today.
+pub fn streaks(sessions: &SessionList, goal: u64, today: NaiveDate) -> Streaks {
+    if goal == 0 {
+        return Streaks {
+            current: 0,
+            longest: 0,
+        };
+    }
+
+    let met: Vec<NaiveDate> = group_by_period(sessions, Period::Day)
+        .into_iter()
+        .filter(|bucket| bucket.pomodoros >= goal)
+        .map(|bucket| bucket.start)
+        .collect();
+
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for &day in &met {
+        run = match previous {
+            Some(previous) if day - previous == TimeDelta::days(1) => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(day);
+    }
+
+    let yesterday = today - TimeDelta::days(1);
+    let current = match previous {
+        Some(last) if last == today || last == yesterday => run,
+        _ => 0,
+    };
+
+    Streaks { current, longest }
+}
+
+/// The most cells the progress bar of the daily goal is drawn with. Larger
+/// goals are only shown as text.
+const GOAL_BAR_CELLS: u64 = 20;
+
+/// Describes how far along today's goal is, e.g. `3/8 pomodoros today`,
+/// with a bar of a cell for each pomodoro of the goal.
+///
+/// ## Arguments
+/// * done: The pomodoros done today.
+/// * goal: The daily goal, 0 if there is none.
+pub fn goal_progress(done: u64, goal: u64) -> String {
+    if goal == 0 {
+        return format!("{done} pomodoros today");
+    }
+
+    let mut progress = format!("{done}/{goal} pomodoros today");
+    if goal <= GOAL_BAR_CELLS {
+        let filled = done.min(goal) as usize;
+        progress.push_str(&format!(
+            " {}{}",
+            "■".repeat(filled),
+            "□".repeat(goal as usize - filled)
+        ));
+    }
+    if done >= goal {
+        progress.push_str(" Goal reached!");
+    }
+
+    progress
+}
+
+/// The amount of weeks shown by the heatmap.
+const HEATMAP_WEEKS: i64 = 52;
+
+/// The colors of the heatmap, from no focus to the most focus.
+const HEATMAP_COLORS: [Color; 5] = [
+    Color::Rgb {
+        r: 45,
+        g: 51,
+        b: 59,
+    },
+    Color::Rgb {
+        r: 14,
+        g: 68,
+        b: 41,
+    },
+    Color::Rgb {
+        r: 0,
+        g: 109,
+        b: 50,
+    },
+    Color::Rgb {
+        r: 38,
+        g: 166,
+        b: 65,
+    },
+    Color::Rgb {
+        r: 57,
+        g: 211,
+        b: 83,
+    },
+];
+
+/// Builds the grid of the heatmap, for the last 52 weeks up to `today`.
+///
+/// ## Returns
+/// A row for each day of the week, starting with Monday, and a column for
+/// each week, oldest first. Each cell holds the level of focus that day from
+/// 0 to 4, relative to the day with the most focus, or `None` if the day is
+/// after today.
+pub fn heatmap_grid(sessions: &SessionList, today: NaiveDate) -> Vec<Vec<Option<usize>>> {
+    let first = Period::Week.start_of(today) - TimeDelta::weeks(HEATMAP_WEEKS - 1);
+    let days: Vec<(NaiveDate, u64)> = group_by_period(sessions, Period::Day)
+        .into_iter()
+        .filter(|bucket| bucket.start >= first && bucket.start <= today)
+        .map(|bucket| (bucket.start, bucket.work_seconds))
+        .collect();
+    let most = days.iter().map(|(_, seconds)| *seconds).max().unwrap_or(0);
+
+    (0..7)
+        .map(|weekday| {
+            (0..HEATMAP_WEEKS)
+                .map(|week| {
+                    let date = first + TimeDelta::weeks(week) + TimeDelta::days(weekday);
+                    if date > today {
+                        return None;
+                    }
+
+                    let seconds = days
+                        .iter()
+                        .find(|(day, _)| *day == date)
+                        .map_or(0, |(_, seconds)| *seconds);
+                    Some(heatmap_level(seconds, most))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Finds the level of focus from 0 to 4 of a day, where days with any focus
+/// are at least 1, and the day with the most focus is 4.
+fn heatmap_level(seconds: u64, most: u64) -> usize {
+    if seconds == 0 || most == 0 {
+        return 0;
+    }
+
+    (seconds * 4).div_ceil(most) as usize
+}
+
+/// The focus of each day of the last 52 weeks, as shown by
+/// `tomato stats --heatmap`.
+pub struct Heatmap {
+    pub grid: Vec<Vec<Option<usize>>>,
+    // The days with any focus in the heatmap, oldest first.
+    pub days: Vec<(NaiveDate, u64)>,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl Heatmap {
+    /// Builds the heatmap of the last 52 weeks up to `today`.
+    pub fn new(sessions: &SessionList, today: NaiveDate) -> Heatmap {
+        let from = Period::Week.start_of(today) - TimeDelta::weeks(HEATMAP_WEEKS - 1);
+        let days = group_by_period(sessions, Period::Day)
+            .into_iter()
+            .filter(|bucket| bucket.start >= from && bucket.start <= today)
+            .filter(|bucket| bucket.work_seconds > 0)
+            .map(|bucket| (bucket.start, bucket.work_seconds))
+            .collect();
+
+        Heatmap {
+            grid: heatmap_grid(sessions, today),
+            days,
+            from,
+            to: today,
+        }
+    }
+
+    /// The focus of all days in the heatmap.
+    fn total_seconds(&self) -> u64 {
+        self.days.iter().map(|(_, seconds)| seconds).sum()
+    }
+}
+
+impl Render for Heatmap {
+    /// A colored grid, with a column for each week.
+    fn table(&self) -> String {
+        let labels = ["Mon", "", "Wed", "", "Fri", "", "Sun"];
+        let mut table = String::new();
+
+        for (label, row) in labels.iter().zip(&self.grid) {
+            let cells: String = row
+                .iter()
+                .map(|cell| match cell {
+                    Some(level) => "■".with(HEATMAP_COLORS[*level]).to_string(),
+                    None => " ".to_string(),
+                })
+                .collect();
+            table.push_str(&format!("{label:<4}{cells}\n"));
+        }
+
+        let legend: String = HEATMAP_COLORS
+            .iter()
+            .map(|color| "■".with(*color).to_string())
+            .collect();
+        table.push_str(&format!("\n    Less {legend} More\n"));
+        table.push_str(&format!(
+            "    {} of focus in the last year\n",
+            format_duration(self.total_seconds())
+        ));
+
+        table
+    }
+
+    /// A line with the date and the seconds of focus of each day with any.
+    fn plain(&self) -> String {
+        self.days
+            .iter()
+            .map(|(date, seconds)| format!("{date}\t{seconds}\n"))
+            .collect()
+    }
+
+    fn json(&self) -> Value {
+        let days: Vec<Value> = self
+            .days
+            .iter()
+            .map(|(date, seconds)| json!({"date": date.to_string(), "focus_seconds": seconds}))
+            .collect();
+
+        json!({
+            "from": self.from.to_string(),
+            "to": self.to.to_string(),
+            "total_focus_seconds": self.total_seconds(),
+            "days": days,
+        })
+    }
+}
+
+/// The names of the days of the week, starting with Monday.
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// The width of the longest bar of a histogram.
+const BAR_WIDTH: u64 = 40;
+
+/// The focus of all sessions by the local hour of the day and the day of the
+/// week it happened in.
+#[derive(Debug, PartialEq)]
+pub struct Histograms {
+    // Seconds of focus in each hour, from 0:00 to 23:00.
+    pub by_hour: [u64; 24],
+    // Seconds of focus on each day of the week, starting with Monday.
+    pub by_weekday: [u64; 7],
+}
+
+impl Histograms {
+    /// Adds up when the sessions were focused on. Sessions are recorded when
+    /// their work ends, so the work is counted back from the timestamp, and
+    /// split between the hours it spans.
+    pub fn new(sessions: &SessionList) -> Histograms {
+        let mut histograms = Histograms {
+            by_hour: [0; 24],
+            by_weekday: [0; 7],
+        };
+
+        for session in sessions.iter() {
+            let end = session.timestamp.with_timezone(&Local);
+            // Work which would start before the earliest date, e.g. from an
+            // imported file, cannot be placed in the day.
+            let Some(mut at) = seconds_before(end, session.work_seconds) else {
+                continue;
+            };
+
+            while at < end {
+                // The start of the next hour, or the end of the work.
+                let next_hour = at
+                    .with_minute(0)
+                    .and_then(|at| at.with_second(0))
+                    .and_then(|at| at.with_nanosecond(0))
+                    .map_or(end, |hour| hour + TimeDelta::hours(1))
+                    .min(end);
+                let seconds = (next_hour - at).num_seconds().max(1) as u64;
+
+                histograms.by_hour[at.hour() as usize] += seconds;
+                histograms.by_weekday[at.weekday().num_days_from_monday() as usize] += seconds;
+                match at.checked_add_signed(TimeDelta::seconds(seconds as i64)) {
+                    Some(next) => at = next,
+                    None => break,
+                }
+            }
+        }
+
+        histograms
+    }
+
+    /// The labels of the hours, e.g. `09:00`.
+    fn hours() -> Vec<String> {
+        (0..24).map(|hour| format!("{hour:02}:00")).collect()
+    }
+}
+
+impl Render for Histograms {
+    /// Bar charts of the focus by hour and by day of the week.
+    fn table(&self) -> String {
+        let weekdays: Vec<String> = WEEKDAYS.iter().map(|day| day.to_string()).collect();
+
+        format!(
+            "Focus by hour of the day:\n{}\nFocus by day of the week:\n{}",
+            bars(&Histograms::hours(), &self.by_hour),
+            bars(&weekdays, &self.by_weekday)
+        )
+    }
+
+    /// A line with `hour` or `weekday`, the label and the seconds of focus.
+    fn plain(&self) -> String {
+        let hours = Histograms::hours()
+            .into_iter()
+            .zip(self.by_hour)
+            .map(|(hour, seconds)| format!("hour\t{hour}\t{seconds}\n"));
+        let weekdays = WEEKDAYS
+            .iter()
+            .zip(self.by_weekday)
+            .map(|(weekday, seconds)| format!("weekday\t{weekday}\t{seconds}\n"));
+
+        hours.chain(weekdays).collect()
+    }
+
+    fn json(&self) -> Value {
+        let by_hour: Vec<Value> = self
+            .by_hour
+            .iter()
+            .enumerate()
+            .map(|(hour, seconds)| json!({"hour": hour, "focus_seconds": seconds}))
+            .collect();
+        let by_weekday: Vec<Value> = WEEKDAYS
+            .iter()
+            .zip(self.by_weekday)
+            .map(|(weekday, seconds)| json!({"weekday": weekday, "focus_seconds": seconds}))
+            .collect();
+
+        json!({"by_hour": by_hour, "by_weekday": by_weekday})
+    }
+}
+
+/// Draws a bar for each label, where the longest bar is `BAR_WIDTH` long.
+fn bars(labels: &[String], seconds: &[u64]) -> String {
+    let most = seconds.iter().copied().max().unwrap_or(0);
+    let mut bars = String::new();
+
+    for (label, seconds) in labels.iter().zip(seconds) {
+        let width = match most {
+            0 => 0,
+            most => (seconds * BAR_WIDTH).div_ceil(most) as usize,
+        };
+        bars.push_str(&format!(
+            "{label:>5} {:<width$} {}\n",
+            "█".repeat(width),
+            format_duration(*seconds),
+            width = BAR_WIDTH as usize
+        ));
+    }
+
+    bars
+}
+
+/// The pomodoros, focus time and break time per period, as shown by
+/// `tomato stats --period`.
+pub struct Breakdown {
+    pub period: Period,
+    pub buckets: Vec<Bucket>,
+}
+
+impl Breakdown {
+    pub fn new(sessions: &SessionList, period: Period) -> Breakdown {
+        Breakdown {
+            period,
+            buckets: group_by_period(sessions, period),
+        }
+    }
+}
+
+impl Render for Breakdown {
+    fn table(&self) -> String {
+        if self.buckets.is_empty() {
+            return "No sessions recorded yet.".to_string();
+        }
+
+        let mut table = format!(
+            "{:<10}  {:>9}  {:>8}  {:>8}\n",
+            self.period.heading(),
+            "Pomodoros",
+            "Focus",
+            "Breaks"
+        );
+        for bucket in &self.buckets {
+            table.push_str(&format!(
+                "{:<10}  {:>9}  {:>8}  {:>8}\n",
+                self.period.label(bucket.start),
+                bucket.pomodoros,
+                format_duration(bucket.work_seconds),
+                format_duration(bucket.break_seconds)
+            ));
+        }
+
+        table
+    }
+
+    /// A line with the period, pomodoros, focus and break seconds.
+    fn plain(&self) -> String {
+        self.buckets
+            .iter()
+            .map(|bucket| {
+                format!(
+                    "{}\t{}\t{}\t{}\n",
+                    self.period.label(bucket.start),
+                    bucket.pomodoros,
+                    bucket.work_seconds,
+                    bucket.break_seconds
+                )
+            })
+            .collect()
+    }
+
+    fn json(&self) -> Value {
+        let buckets: Vec<Value> = self
+            .buckets
+            .iter()
+            .map(|bucket| {
+                json!({
+                    "period": self.period.label(bucket.start),
+                    "start": bucket.start.to_string(),
+                    "pomodoros": bucket.pomodoros,
+                    "work_seconds": bucket.work_seconds,
+                    "break_seconds": bucket.break_seconds,
+                })
+            })
+            .collect();
+
+        json!({"period": self.period.heading().to_lowercase(), "buckets": buckets})
+    }
+}
+
+/// The statistics of all sessions, as shown by `tomato stats`.
+pub struct Summary {
+    pub total_work_seconds: u64,
+    pub pomodoros_today: u64,
+    // 0 if there is no daily goal.
+    pub daily_goal: u64,
+    pub streaks: Streaks,
+    pub by_project: BTreeMap<String, u64>,
+    pub by_tag: BTreeMap<String, u64>,
+}
+
+impl Summary {
+    pub fn new(sessions: &SessionList, settings: &Settings, today: NaiveDate) -> Summary {
+        Summary {
+            total_work_seconds: sessions.total_work_seconds(),
+            pomodoros_today: sessions.pomodoros_on(today),
+            daily_goal: settings.daily_goal,
+            streaks: streaks(sessions, settings.daily_goal, today),
+            by_project: sessions.work_seconds_by_project(),
+            by_tag: sessions.work_seconds_by_tag(),
+        }
+    }
+}
+
+impl Render for Summary {
+    fn table(&self) -> String {
+        let minutes = self.total_work_seconds / 60;
+        let mut table = format!(
+            "You've worked for {} days, {} hours and {} minutes.\n",
+            (minutes / (60 * 24)), // Automatically rounds down
+            (minutes % (60 * 24)) / 60,
+            minutes % 60
+        );
+
+        if minutes == 0 {
+            table.push_str("It's almost better than nothing!\n");
+        } else if minutes <= 25 {
+            table.push_str("It's better than nothing!\n");
+        } else {
+            table.push_str("Good job!\n");
+        }
+
+        if self.daily_goal > 0 {
+            table.push_str(&format!(
+                "\n{}\n",
+                goal_progress(self.pomodoros_today, self.daily_goal)
+            ));
+            table.push_str(&format!("Current streak: {} days\n", self.streaks.current));
+            table.push_str(&format!("Longest streak: {} days\n", self.streaks.longest));
+        }
+
+        for (title, totals) in [("By project:", &self.by_project), ("By tag:", &self.by_tag)] {
+            if totals.is_empty() {
+                continue;
+            }
+
+            table.push_str(&format!("\n{title}\n"));
+            for (name, seconds) in totals {
+                table.push_str(&format!("   {name}: {}\n", format_duration(*seconds)));
+            }
+        }
+
+        table
+    }
+
+    /// A line with the name and the value of each statistic. Projects and
+    /// tags get a line each, with their name and seconds of focus.
+    fn plain(&self) -> String {
+        let mut plain = format!(
+            "total_work_seconds\t{}\npomodoros_today\t{}\ndaily_goal\t{}\ncurrent_streak\t{}\nlongest_streak\t{}\n",
+            self.total_work_seconds,
+            self.pomodoros_today,
+            self.daily_goal,
+            self.streaks.current,
+            self.streaks.longest
+        );
+        for (name, seconds) in &self.by_project {
+            plain.push_str(&format!("project\t{name}\t{seconds}\n"));
+        }
+        for (name, seconds) in &self.by_tag {
+            plain.push_str(&format!("tag\t{name}\t{seconds}\n"));
+        }
+
+        plain
+    }
+
+    fn json(&self) -> Value {
+        json!({
+            "total_work_seconds": self.total_work_seconds,
+            "pomodoros_today": self.pomodoros_today,
+            "daily_goal": self.daily_goal,
+            "current_streak": self.streaks.current,
+            "longest_streak": self.streaks.longest,
+            "by_project": self.by_project,
+            "by_tag": self.by_tag,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn session_at(month: u32, day: u32, hour: u32, work_seconds: u64) -> Session {
+        let timestamp = Local
+            .with_ymd_and_hms(2025, month, day, hour, 0, 0)
+            .single()
+            .unwrap()
+            .with_timezone(&Utc);
+
+        Session::new(Some(timestamp), work_seconds, 5 * 60)
+    }
+
+    #[test]
+    fn test_period_start_of() {
+        // 2025-01-15 is a Wednesday.
+        let day = date(2025, 1, 15);
+
+        assert_eq!(Period::Day.start_of(day), day);
+        assert_eq!(Period::Week.start_of(day), date(2025, 1, 13));
+        assert_eq!(Period::Month.start_of(day), date(2025, 1, 1));
+        assert_eq!(Period::Year.start_of(day), date(2025, 1, 1));
+    }
+
+    #[test]
+    fn test_period_label() {
+        assert_eq!(Period::Day.label(date(2025, 1, 6)), "2025-01-06");
+        assert_eq!(Period::Week.label(date(2025, 1, 6)), "2025-W02");
+        // The week of 2024-12-30 is the first week of 2025.
+        assert_eq!(Period::Week.label(date(2024, 12, 30)), "2025-W01");
+        assert_eq!(Period::Month.label(date(2025, 1, 1)), "2025-01");
+        assert_eq!(Period::Year.label(date(2025, 1, 1)), "2025");
+    }
+
+    #[test]
+    fn test_group_by_period_uses_local_dates() {
+        let sessions = SessionList::new(Some(vec![
+            session_at(1, 13, 23, 25 * 60),
+            session_at(1, 14, 0, 25 * 60),
+            Session {
+                status: SessionStatus::Aborted,
+                ..session_at(1, 14, 9, 10 * 60)
+            },
+            session_at(2, 3, 9, 50 * 60),
+        ]));
+
+        let days = group_by_period(&sessions, Period::Day);
+        assert_eq!(days.len(), 3);
+        assert_eq!(days[0].start, date(2025, 1, 13));
+        assert_eq!(days[1].pomodoros, 1);
+        assert_eq!(days[1].work_seconds, 35 * 60);
+        assert_eq!(days[1].break_seconds, 10 * 60);
+
+        let months = group_by_period(&sessions, Period::Month);
+        assert_eq!(
+            months,
+            vec![
+                Bucket {
+                    start: date(2025, 1, 1),
+                    pomodoros: 2,
+                    work_seconds: 60 * 60,
+                    break_seconds: 15 * 60,
+                },
+                Bucket {
+                    start: date(2025, 2, 1),
+                    pomodoros: 1,
+                    work_seconds: 50 * 60,
+                    break_seconds: 5 * 60,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_streaks() {
+        let sessions = SessionList::new(Some(vec![
+            // A streak of 3 days, from the 1st to the 3rd.
+            session_at(1, 1, 9, 25 * 60),
+            session_at(1, 1, 10, 25 * 60),
+            session_at(1, 2, 9, 25 * 60),
+            session_at(1, 2, 10, 25 * 60),
+            session_at(1, 3, 9, 25 * 60),
+            session_at(1, 3, 10, 25 * 60),
+            // The goal was not met on the 4th.
+            session_at(1, 4, 9, 25 * 60),
+            // A streak of 2 days, from the 6th to the 7th.
+            session_at(1, 6, 9, 25 * 60),
+            session_at(1, 6, 10, 25 * 60),
+            session_at(1, 7, 9, 25 * 60),
+            session_at(1, 7, 10, 25 * 60),
+        ]));
+
+        // Today is not over yet, so the streak carries on from yesterday.
+        assert_eq!(
+            streaks(&sessions, 2, date(2025, 1, 8)),
+            Streaks {
+                current: 2,
+                longest: 3
+            }
+        );
+        assert_eq!(streaks(&sessions, 2, date(2025, 1, 7)).current, 2);
+        assert_eq!(streaks(&sessions, 2, date(2025, 1, 9)).current, 0);
+        assert_eq!(streaks(&sessions, 1, date(2025, 1, 8)).longest, 4);
+        assert_eq!(streaks(&sessions, 0, date(2025, 1, 8)).longest, 0);
+    }
+
+    #[test]
+    fn test_goal_progress() {
+        assert_eq!(goal_progress(3, 5), "3/5 pomodoros today ■■■□□");
+        assert_eq!(
+            goal_progress(6, 5),
+            "6/5 pomodoros today ■■■■■ Goal reached!"
+        );
+        assert_eq!(goal_progress(2, 0), "2 pomodoros today");
+        assert_eq!(
+            goal_progress(0, 20),
+            format!("0/20 pomodoros today {}", "□".repeat(20))
+        );
+        assert_eq!(goal_progress(3, 21), "3/21 pomodoros today");
+        assert_eq!(goal_progress(30, 21), "30/21 pomodoros today Goal reached!");
+        assert_eq!(
+            goal_progress(1, u64::MAX),
+            format!("1/{} pomodoros today", u64::MAX)
+        );
+    }
+
+    #[test]
+    fn test_heatmap_level() {
+        assert_eq!(heatmap_level(0, 100), 0);
+        assert_eq!(heatmap_level(1, 100), 1);
+        assert_eq!(heatmap_level(50, 100), 2);
+        assert_eq!(heatmap_level(51, 100), 3);
+        assert_eq!(heatmap_level(100, 100), 4);
+    }
+
+    #[test]
+    fn test_heatmap_grid() {
+        // 2025-01-15 is a Wednesday.
+        let today = date(2025, 1, 15);
+        let sessions = SessionList::new(Some(vec![
+            session_at(1, 13, 9, 100 * 60),
+            session_at(1, 15, 9, 25 * 60),
+            // Too long ago to be shown.
+            Session::new(
+                Some(Utc.with_ymd_and_hms(2023, 1, 2, 12, 0, 0).unwrap()),
+                1000 * 60,
+                0,
+            ),
+        ]));
+
+        let grid = heatmap_grid(&sessions, today);
+        assert_eq!(grid.len(), 7);
+        assert!(grid.iter().all(|row| row.len() == 52));
+
+        // The last column is the week of today.
+        assert_eq!(grid[0][51], Some(4));
+        assert_eq!(grid[1][51], Some(0));
+        assert_eq!(grid[2][51], Some(1));
+        assert_eq!(grid[3][51], None);
+        assert_eq!(grid[6][51], None);
+        assert_eq!(grid[0][0], Some(0));
+    }
+
+    #[test]
+    fn test_histograms_split_work_between_hours() {
+        // 2025-01-13 is a Monday. The work runs from 8:40 to 10:00.
+        let sessions = SessionList::new(Some(vec![
+            session_at(1, 13, 10, 80 * 60),
+            // From 23:30 on Tuesday to 0:30 on Wednesday.
+            Session::new(
+                Some(
+                    Local
+                        .with_ymd_and_hms(2025, 1, 15, 0, 30, 0)
+                        .single()
+                        .unwrap()
+                        .with_timezone(&Utc),
+                ),
+                60 * 60,
+                0,
+            ),
+        ]));
+
+        let histograms = Histograms::new(&sessions);
+        assert_eq!(histograms.by_hour[8], 20 * 60);
+        assert_eq!(histograms.by_hour[9], 60 * 60);
+        assert_eq!(histograms.by_hour[23], 30 * 60);
+        assert_eq!(histograms.by_hour[0], 30 * 60);
+        assert_eq!(histograms.by_hour.iter().sum::<u64>(), 140 * 60);
+        assert_eq!(histograms.by_weekday[0], 80 * 60);
+        assert_eq!(histograms.by_weekday[1], 30 * 60);
+        assert_eq!(histograms.by_weekday[2], 30 * 60);
+    }
+
+    #[test]
+    fn test_histograms_skip_work_longer_than_the_range_of_dates() {
+        let sessions = SessionList::new(Some(vec![
+            session_at(1, 13, 10, 25 * 60),
+            session_at(1, 13, 10, u64::MAX),
+        ]));
+
+        let histograms = Histograms::new(&sessions);
+        assert_eq!(histograms.by_hour.iter().sum::<u64>(), 25 * 60);
+    }
+
+    #[test]
+    fn test_histograms_to_json() {
+        let sessions = SessionList::new(Some(vec![session_at(1, 13, 10, 25 * 60)]));
+        let json = Histograms::new(&sessions).json();
+
+        assert_eq!(json["by_hour"].as_array().unwrap().len(), 24);
+        assert_eq!(
+            json["by_hour"][9],
+            json!({"hour": 9, "focus_seconds": 1500})
+        );
+        assert_eq!(
+            json["by_weekday"][0],
+            json!({"weekday": "Mon", "focus_seconds": 1500})
+        );
+    }
+
+    #[test]
+    fn test_breakdown_json_and_plain() {
+        let sessions = SessionList::new(Some(vec![session_at(1, 13, 9, 25 * 60)]));
+        let breakdown = Breakdown::new(&sessions, Period::Week);
+
+        assert_eq!(
+            breakdown.json(),
+            json!({"period": "week", "buckets": [{
+                "period": "2025-W03",
+                "start": "2025-01-13",
+                "pomodoros": 1,
+                "work_seconds": 1500,
+                "break_seconds": 300,
+            }]})
+        );
+        assert_eq!(breakdown.plain(), "2025-W03\t1\t1500\t300\n");
+    }
+
+    #[test]
+    fn test_summary_json() {
+        let sessions = SessionList::new(Some(vec![
+            session_at(1, 13, 9, 25 * 60).with_labels(Some("acme".to_string()), Vec::new())
+        ]));
+        let mut settings = Settings::new(25, 5, Default::default());
+        settings.daily_goal = 1;
+        let summary = Summary::new(&sessions, &settings, date(2025, 1, 13));
+
+        assert_eq!(
+            summary.json(),
+            json!({
+                "total_work_seconds": 1500,
+                "pomodoros_today": 1,
+                "daily_goal": 1,
+                "current_streak": 1,
+                "longest_streak": 1,
+                "by_project": {"acme": 1500},
+                "by_tag": {},
+            })
+        );
+    }
+
+    #[test]
+    fn test_heatmap_json_lists_days_with_focus() {
+        let sessions = SessionList::new(Some(vec![session_at(1, 13, 9, 25 * 60)]));
+        let heatmap = Heatmap::new(&sessions, date(2025, 1, 15));
+
+        assert_eq!(heatmap.from, date(2024, 1, 22));
+        assert_eq!(
+            heatmap.json()["days"],
+            json!([{"date": "2025-01-13", "focus_seconds": 1500}])
+        );
+        assert_eq!(heatmap.plain(), "2025-01-13\t1500\n");
+    }
+
+    #[test]
+    fn test_group_by_period_sorts_out_of_order_sessions() {
+        let sessions = SessionList::new(Some(vec![
+            session_at(3, 1, 9, 25 * 60),
+            session_at(1, 1, 9, 25 * 60),
+        ]));
+
+        let years = group_by_period(&sessions, Period::Year);
+        assert_eq!(years.len(), 1);
+        assert_eq!(years[0].pomodoros, 2);
+
+        let months = group_by_period(&sessions, Period::Month);
+        assert_eq!(months[0].start, date(2025, 1, 1));
+        assert_eq!(months[1].start, date(2025, 3, 1));
+    }
+}
@@ -0,0 +1,348 @@
+//! # Status
+//! This file contains the status of the timer, as shown by `tomato status`
+//! in status bars such as waybar, polybar, tmux or i3blocks.
+//!
+//! The status is read from the daemon if it is running, and otherwise from
+//! `current.json`, which is kept up to date by timers in the foreground.
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::daemon::{self, Request};
+use crate::render::Render;
+use crate::state::{CurrentState, Phase};
+use crate::timers::{self, SAVE_INTERVAL};
+
+/// The template used when `--format` is not given.
+pub const DEFAULT_FORMAT: &str = "{icon} {remaining}";
+
+/// A state which has not been updated for this many save intervals belongs
+/// to a timer which was interrupted.
+const STALE_INTERVALS: u32 = 3;
+
+/// What the timer is doing, as shown in a status bar.
+#[derive(Debug, PartialEq)]
+pub struct Status {
+    // `None` if no timer is running.
+    pub phase: Option<Phase>,
+    pub paused: bool,
+    // The timer stopped updating, e.g. because its terminal was closed.
+    pub interrupted: bool,
+    pub remaining_seconds: u64,
+    pub duration_seconds: u64,
+    // The amount of pomodoros finished today.
+    pub count: u64,
+}
+
+impl Status {
+    /// Creates the status from the state of the running timer at `now`.
+    ///
+    /// ## Arguments
+    /// * state: The state of the running timer, or `None` if it is idle.
+    /// * count: The amount of pomodoros finished today.
+    pub fn new(state: Option<&CurrentState>, count: u64, now: DateTime<Utc>) -> Status {
+        let Some(state) = state else {
+            return Status {
+                phase: None,
+                paused: false,
+                interrupted: false,
+                remaining_seconds: 0,
+                duration_seconds: 0,
+                count,
+            };
+        };
+
+        Status {
+            phase: Some(state.phase),
+            paused: state.paused,
+            interrupted: timers::since(state.updated_at, now) > SAVE_INTERVAL * STALE_INTERVALS,
+            remaining_seconds: state.remaining_at(now),
+            duration_seconds: state.duration_seconds,
+            count,
+        }
+    }
+
+    /// The name of what the timer is doing, e.g. `work`, `paused` or `idle`.
+    /// Used as the CSS class in waybar.
+    pub fn class(&self) -> &'static str {
+        match self.phase {
+            None => "idle",
+            Some(_) if self.interrupted => "interrupted",
+            Some(_) if self.paused => "paused",
+            Some(phase) => phase.name(),
+        }
+    }
+
+    /// An icon for what the timer is doing.
+    pub fn icon(&self) -> &'static str {
+        match self.class() {
+            "work" => "🍅",
+            "break" => "☕",
+            "paused" => "⏸",
+            "interrupted" => "⚠",
+            _ => "💤",
+        }
+    }
+
+    /// How much of the current phase is done, from 0 to 100.
+    pub fn percentage(&self) -> u64 {
+        if self.duration_seconds == 0 {
+            return 0;
+        }
+
+        (self.duration_seconds - self.remaining_seconds) * 100 / self.duration_seconds
+    }
+
+    /// Fills in a template, e.g. `{phase} {remaining} {count}`.
+    ///
+    /// ## Placeholders
+    /// * `{phase}`: `work`, `break` or `idle`.
+    /// * `{state}`: `work`, `break`, `paused`, `interrupted` or `idle`.
+    /// * `{icon}`: An icon for the state.
+    /// * `{remaining}`: The time left of the phase, e.g. `12:34`.
+    /// * `{percentage}`: How much of the phase is done, from 0 to 100.
+    /// * `{count}`: The amount of pomodoros finished today.
+    pub fn format(&self, template: &str) -> String {
+        let phase = self.phase.map_or("idle", |phase| phase.name());
+        let template = match self.phase {
+            Some(_) => template.replace(
+                "{remaining}",
+                &timers::format_remaining(Duration::from_secs(self.remaining_seconds)),
+            ),
+            // Nothing is left when idle.
+            None => remove_placeholder(template, "{remaining}"),
+        };
+
+        template
+            .replace("{phase}", phase)
+            .replace("{state}", self.class())
+            .replace("{icon}", self.icon())
+            .replace("{percentage}", &self.percentage().to_string())
+            .replace("{count}", &self.count.to_string())
+    }
+
+    /// Formats the status as the JSON a waybar custom module expects, with
+    /// the text filled in from `template`.
+    pub fn to_waybar_json(&self, template: &str) -> String {
+        let tooltip = match self.phase {
+            Some(_) => format!(
+                "{} — {} pomodoros today",
+                self.format("{state}: {remaining} left"),
+                self.count
+            ),
+            None => format!("No timer running — {} pomodoros today", self.count),
+        };
+
+        json!({
+            "text": self.format(template),
+            "alt": self.class(),
+            "class": self.class(),
+            "tooltip": tooltip,
+            "percentage": self.percentage(),
+        })
+        .to_string()
+    }
+}
+
+/// The status, as shown by `tomato status` with `--output`.
+pub struct StatusView<'a> {
+    pub status: &'a Status,
+    // The template of the text, see `Status::format`.
+    pub template: &'a str,
+}
+
+impl Render for StatusView<'_> {
+    /// The template, filled in.
+    fn table(&self) -> String {
+        self.status.format(self.template)
+    }
+
+    /// The state, remaining seconds, duration in seconds, percentage and
+    /// count, on one line.
+    fn plain(&self) -> String {
+        let status = self.status;
+        format!(
+            "{}\t{}\t{}\t{}\t{}",
+            status.class(),
+            status.remaining_seconds,
+            status.duration_seconds,
+            status.percentage(),
+            status.count
+        )
+    }
+
+    fn json(&self) -> Value {
+        let status = self.status;
+        json!({
+            "phase": status.phase.map(|phase| phase.name()),
+            "state": status.class(),
+            "paused": status.paused,
+            "interrupted": status.interrupted,
+            "remaining_seconds": status.remaining_seconds,
+            "duration_seconds": status.duration_seconds,
+            "percentage": status.percentage(),
+            "count": status.count,
+            "text": status.format(self.template),
+        })
+    }
+}
+
+/// Finds the state of the running timer, asking the daemon first, and
+/// reading `current.json` if it is not running.
+pub fn current_state() -> Option<CurrentState> {
+    match daemon::send(&Request::Status) {
+        Ok(response) => response.state,
+        Err(_) => CurrentState::load(),
+    }
+}
+
+/// Removes a placeholder which is empty from a template, along with the
+/// gap it would leave: the spaces before or after it, so that the text
+/// around it is separated by one space, and none at the start or end of a
+/// line. Other whitespace in the template is kept.
+fn remove_placeholder(template: &str, placeholder: &str) -> String {
+    let mut parts = template.split(placeholder);
+    let mut text = parts.next().unwrap_or_default().to_string();
+
+    for part in parts {
+        let part = if text.is_empty() || text.ends_with(['\n', ' ']) {
+            part.trim_start_matches(' ')
+        } else {
+            part
+        };
+        if part.is_empty() || part.starts_with('\n') {
+            text.truncate(text.trim_end_matches(' ').len());
+        }
+        text.push_str(part);
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::from_timestamp(1735689600, 0).unwrap()
+    }
+
+    fn state(phase: Phase, elapsed_seconds: u64) -> CurrentState {
+        let mut state = CurrentState::new(phase, 25 * 60, 25 * 60, 0);
+        state.elapsed_seconds = elapsed_seconds;
+        state.updated_at = now();
+        state
+    }
+
+    #[test]
+    fn test_status_idle() {
+        let status = Status::new(None, 3, now());
+
+        assert_eq!(status.class(), "idle");
+        assert_eq!(status.format("{phase} {remaining} {count}"), "idle 3");
+        assert_eq!(status.format(DEFAULT_FORMAT), "💤");
+    }
+
+    #[test]
+    fn test_status_idle_keeps_other_whitespace() {
+        let status = Status::new(None, 3, now());
+
+        assert_eq!(
+            status.format("{icon}  {remaining}\n\t{count} today\n{remaining} {phase}"),
+            "💤\n\t3 today\nidle"
+        );
+        assert_eq!(status.format("{phase}:  {count}"), "idle:  3");
+        assert_eq!(status.format("[{remaining}]"), "[]");
+    }
+
+    #[test]
+    fn test_status_keeps_whitespace_when_running() {
+        let state = state(Phase::Work, 60);
+        let status = Status::new(Some(&state), 2, now());
+
+        assert_eq!(
+            status.format(" {icon}\t{remaining}\n{count}  today "),
+            " 🍅\t24:00\n2  today "
+        );
+    }
+
+    #[test]
+    fn test_status_counts_down_since_last_update() {
+        let state = state(Phase::Work, 60);
+        let status = Status::new(Some(&state), 2, now() + TimeDelta::seconds(5));
+
+        assert_eq!(status.remaining_seconds, 24 * 60 - 5);
+        assert_eq!(status.format("{phase} {remaining} {count}"), "work 23:55 2");
+        assert_eq!(status.percentage(), 4);
+    }
+
+    #[test]
+    fn test_status_paused_does_not_count_down() {
+        let mut state = state(Phase::Break, 60);
+        state.paused = true;
+        let status = Status::new(Some(&state), 0, now() + TimeDelta::seconds(20));
+
+        assert_eq!(status.remaining_seconds, 24 * 60);
+        assert_eq!(status.class(), "paused");
+        assert_eq!(status.format("{phase} {state}"), "break paused");
+    }
+
+    #[test]
+    fn test_status_interrupted_when_not_updated() {
+        let state = state(Phase::Work, 60);
+        let status = Status::new(Some(&state), 0, now() + TimeDelta::minutes(5));
+
+        assert!(status.interrupted);
+        assert_eq!(status.class(), "interrupted");
+    }
+
+    #[test]
+    fn test_status_view_json_and_plain() {
+        let state = state(Phase::Break, 60);
+        let status = Status::new(Some(&state), 4, now());
+        let view = StatusView {
+            status: &status,
+            template: "{remaining}",
+        };
+
+        assert_eq!(
+            view.json(),
+            json!({
+                "phase": "break",
+                "state": "break",
+                "paused": false,
+                "interrupted": false,
+                "remaining_seconds": 1440,
+                "duration_seconds": 1500,
+                "percentage": 4,
+                "count": 4,
+                "text": "24:00",
+            })
+        );
+        assert_eq!(view.plain(), "break\t1440\t1500\t4\t4");
+
+        let idle = Status::new(None, 0, now());
+        let view = StatusView {
+            status: &idle,
+            template: DEFAULT_FORMAT,
+        };
+        assert_eq!(view.json()["phase"], Value::Null);
+    }
+
+    #[test]
+    fn test_status_to_waybar_json() {
+        let state = state(Phase::Work, 0);
+        let status = Status::new(Some(&state), 1, now());
+        let json: serde_json::Value =
+            serde_json::from_str(&status.to_waybar_json("{remaining}")).unwrap();
+
+        assert_eq!(json["text"], "25:00");
+        assert_eq!(json["class"], "work");
+        assert_eq!(json["alt"], "work");
+        assert_eq!(json["percentage"], 0);
+        assert_eq!(json["tooltip"], "work: 25:00 left — 1 pomodoros today");
+    }
+}
@@ -2,13 +2,19 @@
 //! This file contains the necessary functions to create persistent storage for
 //! Tomato.
 //!
-//! Tomato uses a JSON file to store the sessions a user has had.
+//! Tomato uses a JSON Lines file to store the sessions a user has had, and
+//! JSON files for everything else. Files which are written whole are
+//! replaced in one step, keeping the previous version as a `.bak` file.
 
 use std::fs;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::io::prelude::*;
+use std::io::{BufReader, SeekFrom};
 use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
 
+use chrono::Local;
 use home::home_dir;
 
 /// Using the `home` crate, finds the home folder for the current user.
@@ -39,6 +45,47 @@ fn folder_exists(folder: String) -> bool {
     Path::new(&path).exists()
 }
 
+/// Finds where the last line of a file starts. A newline at the very end
+/// of the file ends the last line, rather than starting a new one.
+///
+/// ## Returns
+/// The offset of the last line, which is 0 if the file has a single line.
+fn last_line_start(file: &mut File) -> std::io::Result<u64> {
+    let mut end = file.metadata()?.len().saturating_sub(1);
+    let mut buffer = [0; 4096];
+
+    while end > 0 {
+        let start = end.saturating_sub(buffer.len() as u64);
+        let chunk = &mut buffer[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+
+        if let Some(newline) = chunk.iter().rposition(|byte| *byte == b'\n') {
+            return Ok(start + newline as u64 + 1);
+        }
+        end = start;
+    }
+
+    Ok(0)
+}
+
+/// The folder in the home folder which Tomato keeps its files in. Tests keep
+/// theirs apart, so that they never touch the files of the user.
+#[cfg(not(test))]
+pub const DEFAULT_FOLDER: &str = ".tomato";
+#[cfg(test)]
+pub const DEFAULT_FOLDER: &str = ".tomato_test_default";
+
+/// How many temporary files this process has made, to give each a name of
+/// its own.
+static TEMPORARY_FILES: AtomicU64 = AtomicU64::new(0);
+
+/// An advisory lock on a storage file, which other instances of Tomato wait
+/// for. It is released when it is dropped.
+pub struct FileLock {
+    _file: File,
+}
+
 /// Holds the variable containing the path to the storage file.
 pub struct Storage {
     storage_file: String,
@@ -58,38 +105,199 @@ impl Storage {
             storage_file: format!(
                 "{}/{}/{}",
                 get_home_path_with(home_dir),
-                folder.clone().unwrap_or(".tomato".to_string()),
+                folder.clone().unwrap_or(DEFAULT_FOLDER.to_string()),
                 path
             ),
-            folder: folder.unwrap_or(".tomato".to_string()),
+            folder: folder.unwrap_or(DEFAULT_FOLDER.to_string()),
         }
     }
 
-    /// Writes to `storage_file`.
+    /// The path to `storage_file`.
+    pub fn path(&self) -> &Path {
+        Path::new(&self.storage_file)
+    }
+
+    /// Creates the folder of `storage_file`, if it does not exist yet.
     ///
     /// ## Returns
-    /// A Result value. Ok(()) if no problems occured, otherwise Err.
-    pub fn write(&self, text: String) -> std::io::Result<()> {
+    /// A Result value. Ok(()) if the folder exists, otherwise Err.
+    pub fn create_folder(&self) -> std::io::Result<()> {
         if !folder_exists(self.folder.clone()) {
             let path = format!("{}/{}/", get_home_path_with(home_dir), self.folder);
-            fs::create_dir(path)?;
+            // Another process may create it at the same time.
+            fs::create_dir_all(path)?;
         }
 
-        // File::create creates a file if it does not exist.
-        // If it does exist, it truncates the file.
-        let mut file = File::create(self.storage_file.clone())?;
+        Ok(())
+    }
 
-        let byte_amount = file.write(text.as_bytes())?;
+    /// The path to the copy of `storage_file` from before it was last
+    /// written, e.g. `settings.json.bak`.
+    pub fn backup_path(&self) -> PathBuf {
+        PathBuf::from(format!("{}.bak", self.storage_file))
+    }
 
-        if byte_amount != text.len() {
-            panic!("Something went wrong while writing to file. Written amount not equal to amount which should have been written.");
+    /// Locks `storage_file`, waiting until no other process holds the lock.
+    ///
+    /// The lock is taken on a `.lock` file next to `storage_file`, as
+    /// `write` replaces `storage_file` with a new file, which a lock on the
+    /// old one would not cover. Processes which do not lock the file can
+    /// still change it.
+    ///
+    /// ## Returns
+    /// A Result value. Ok(FileLock) which holds the lock until it is dropped,
+    /// otherwise Err.
+    pub fn lock(&self) -> std::io::Result<FileLock> {
+        self.create_folder()?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(format!("{}.lock", self.storage_file))?;
+        file.lock()?;
+
+        Ok(FileLock { _file: file })
+    }
+
+    /// Writes to `storage_file`.
+    ///
+    /// The text is written to a temporary file next to it, which is synced to
+    /// the disk and then renamed over `storage_file`. A crash or a full disk
+    /// thus leaves either the old or the new contents, never a part of them.
+    /// The old contents are kept in the `.bak` file.
+    ///
+    /// ## Returns
+    /// A Result value. Ok(()) if no problems occured, otherwise Err.
+    pub fn write(&self, text: String) -> std::io::Result<()> {
+        let temporary = self.write_temporary(&text, true)?;
+
+        if self.path().exists() {
+            // A hard link keeps the old contents without copying them, as the
+            // rename below gives `storage_file` a new file.
+            let backup = self.backup_path();
+            let _ = fs::remove_file(&backup);
+            if fs::hard_link(self.path(), &backup).is_err() {
+                if let Err(e) = fs::copy(self.path(), &backup) {
+                    let _ = fs::remove_file(&temporary);
+                    return Err(e);
+                }
+            }
         }
+        self.rename_temporary(&temporary)?;
 
-        file.flush().unwrap();
+        // Makes the rename itself survive a crash. Folders cannot be synced
+        // on every platform, so this is allowed to fail.
+        if let Some(folder) = self.path().parent() {
+            let _ = File::open(folder).and_then(|folder| folder.sync_all());
+        }
 
         Ok(())
     }
 
+    /// Writes to `storage_file`, for files which are written often and only
+    /// matter for a while, such as the state of the running timer. Like
+    /// `write`, it never leaves a part of the text, but it keeps no backup
+    /// and does not wait for the disk.
+    ///
+    /// ## Returns
+    /// A Result value. Ok(()) if no problems occured, otherwise Err.
+    pub fn write_scratch(&self, text: String) -> std::io::Result<()> {
+        let temporary = self.write_temporary(&text, false)?;
+        self.rename_temporary(&temporary)
+    }
+
+    /// Writes the text to a new temporary file next to `storage_file`.
+    ///
+    /// ## Arguments
+    /// * sync: Whether to wait until the text is on the disk.
+    ///
+    /// ## Returns
+    /// A Result value. Ok(String) with the path of the temporary file,
+    /// otherwise Err.
+    fn write_temporary(&self, text: &str, sync: bool) -> std::io::Result<String> {
+        self.create_folder()?;
+
+        // Each write has a temporary file of its own, so that two processes,
+        // or two threads, writing at once do not mix their contents.
+        let temporary = format!(
+            "{}.{}-{}.tmp",
+            self.storage_file,
+            process::id(),
+            TEMPORARY_FILES.fetch_add(1, Ordering::Relaxed)
+        );
+        let written = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temporary)
+            .and_then(|mut file| {
+                file.write_all(text.as_bytes())?;
+                if sync {
+                    file.sync_all()?;
+                }
+                Ok(())
+            });
+
+        match written {
+            Ok(()) => Ok(temporary),
+            Err(e) => {
+                let _ = fs::remove_file(&temporary);
+                Err(e)
+            }
+        }
+    }
+
+    /// Replaces `storage_file` with a temporary file from `write_temporary`.
+    fn rename_temporary(&self, temporary: &str) -> std::io::Result<()> {
+        fs::rename(temporary, self.path()).inspect_err(|_| {
+            let _ = fs::remove_file(temporary);
+        })
+    }
+
+    /// Appends to `storage_file`, creating it if it does not exist.
+    ///
+    /// ## Returns
+    /// A Result value. Ok(()) if no problems occured, otherwise Err.
+    pub fn append(&self, text: String) -> std::io::Result<()> {
+        self.create_folder()?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.storage_file.clone())?;
+
+        file.write_all(text.as_bytes())
+    }
+
+    /// Replaces the last line of `storage_file`, leaving the lines before it
+    /// as they are. The file is read backwards from its end, so this takes
+    /// as long for a short file as for a long one.
+    ///
+    /// ## Arguments
+    /// * text: The new last line, ending with a newline.
+    ///
+    /// ## Returns
+    /// A Result value. Ok(()) if no problems occured, otherwise Err.
+    pub fn replace_last_line(&self, text: String) -> std::io::Result<()> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(self.storage_file.clone())?;
+
+        let start = last_line_start(&mut file)?;
+        file.set_len(start)?;
+        file.seek(SeekFrom::Start(start))?;
+        file.write_all(text.as_bytes())
+    }
+
+    /// Opens `storage_file` to be read line by line.
+    ///
+    /// ## Returns
+    /// A Result value. Ok(BufReader) if the file could be opened, otherwise Err.
+    pub fn reader(&self) -> std::io::Result<BufReader<File>> {
+        Ok(BufReader::new(File::open(self.storage_file.clone())?))
+    }
+
     /// Reads from `storage_file`.
     ///
     /// ## Returns
@@ -103,6 +311,31 @@ impl Storage {
 
         Ok(contents)
     }
+
+    /// Moves `storage_file` out of the way, e.g. because it could not be
+    /// read, to a file named after it and the current time, such as
+    /// `settings.json.corrupt-20250131-140000`.
+    ///
+    /// ## Returns
+    /// A Result value. Ok(PathBuf) with the new path of the file, otherwise Err.
+    pub fn quarantine(&self) -> std::io::Result<PathBuf> {
+        let path = PathBuf::from(format!(
+            "{}.corrupt-{}",
+            self.storage_file,
+            Local::now().format("%Y%m%d-%H%M%S")
+        ));
+        fs::rename(self.path(), &path)?;
+
+        Ok(path)
+    }
+
+    /// Removes `storage_file`.
+    ///
+    /// ## Returns
+    /// A Result value. Ok(()) if the file was removed, otherwise Err.
+    pub fn remove(&self) -> std::io::Result<()> {
+        fs::remove_file(self.storage_file.clone())
+    }
 }
 
 #[cfg(test)]
@@ -160,10 +393,10 @@ mod tests {
         let storage = Storage::new(folder, path.clone());
 
         let home = get_home_path_with(home_dir);
-        let expected_storage_file = format!("{}/{}/{}", home, ".tomato", "file.txt");
+        let expected_storage_file = format!("{}/{}/{}", home, DEFAULT_FOLDER, "file.txt");
 
         assert_eq!(storage.storage_file, expected_storage_file);
-        assert_eq!(storage.folder, ".tomato".to_string());
+        assert_eq!(storage.folder, DEFAULT_FOLDER.to_string());
     }
 
     #[test]
@@ -202,6 +435,190 @@ mod tests {
         ));
     }
 
+    /// Checks whether a write left a temporary file next to `storage`.
+    fn temporary_files_left(storage: &Storage) -> bool {
+        fs::read_dir(storage.path().parent().unwrap())
+            .unwrap()
+            .filter_map(Result::ok)
+            .any(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"))
+    }
+
+    #[test]
+    fn test_storage_writes_at_once_do_not_mix() {
+        let folder = "concurrent_folder";
+        let writers: Vec<_> = (0..8)
+            .map(|writer| {
+                std::thread::spawn(move || {
+                    let storage = Storage::new(Some(folder.to_string()), "file.txt".to_string());
+                    let text = writer.to_string().repeat(100_000);
+                    for _ in 0..10 {
+                        storage.write(text.clone()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let storage = Storage::new(Some(folder.to_string()), "file.txt".to_string());
+        let contents = storage.read().unwrap();
+        assert_eq!(contents.len(), 100_000);
+        let first = contents.chars().next().unwrap();
+        assert!(contents.chars().all(|c| c == first));
+        assert!(!temporary_files_left(&storage));
+
+        let _ = remove_dir_all(format!("{}/{}", get_home_path_with(home_dir), folder));
+    }
+
+    #[test]
+    fn test_storage_write_keeps_a_backup() {
+        let folder = Some("backup_folder".to_string());
+        let storage = Storage::new(folder, "file.txt".to_string());
+
+        storage.write("first".to_string()).unwrap();
+        assert!(!storage.backup_path().exists());
+
+        storage.write("second".to_string()).unwrap();
+        storage.write("third".to_string()).unwrap();
+        assert_eq!(storage.read().unwrap(), "third");
+        assert_eq!(fs::read_to_string(storage.backup_path()).unwrap(), "second");
+        assert!(!temporary_files_left(&storage));
+
+        let _ = remove_dir_all(format!(
+            "{}/{}",
+            get_home_path_with(home_dir),
+            "backup_folder"
+        ));
+    }
+
+    #[test]
+    fn test_storage_lock_waits_for_the_lock_to_be_released() {
+        let folder = Some("lock_folder".to_string());
+        let storage = Storage::new(folder.clone(), "file.txt".to_string());
+        let lock = storage.lock().unwrap();
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let waiting = std::thread::spawn(move || {
+            let storage = Storage::new(folder, "file.txt".to_string());
+            let _lock = storage.lock().unwrap();
+            sender.send(()).unwrap();
+        });
+
+        let timeout = std::time::Duration::from_millis(200);
+        assert!(receiver.recv_timeout(timeout).is_err());
+        drop(lock);
+        assert!(receiver.recv_timeout(timeout * 10).is_ok());
+        waiting.join().unwrap();
+
+        let _ = remove_dir_all(format!(
+            "{}/{}",
+            get_home_path_with(home_dir),
+            "lock_folder"
+        ));
+    }
+
+    #[test]
+    fn test_storage_write_scratch_keeps_no_backup() {
+        let folder = Some("scratch_folder".to_string());
+        let storage = Storage::new(folder, "file.txt".to_string());
+
+        storage.write_scratch("first".to_string()).unwrap();
+        storage.write_scratch("second".to_string()).unwrap();
+        assert_eq!(storage.read().unwrap(), "second");
+        assert!(!storage.backup_path().exists());
+        assert!(!temporary_files_left(&storage));
+
+        let _ = remove_dir_all(format!(
+            "{}/{}",
+            get_home_path_with(home_dir),
+            "scratch_folder"
+        ));
+    }
+
+    #[test]
+    fn test_storage_append() {
+        let folder = Some("append_folder".to_string());
+        let storage = Storage::new(folder, "file.txt".to_string());
+
+        storage.append("Lorem ".to_string()).unwrap();
+        storage.append("ipsum".to_string()).unwrap();
+
+        assert_eq!(storage.read().unwrap(), "Lorem ipsum");
+
+        let _ = remove_dir_all(format!(
+            "{}/{}",
+            get_home_path_with(home_dir),
+            "append_folder"
+        ));
+    }
+
+    #[test]
+    fn test_storage_replace_last_line() {
+        let folder = Some("replace_folder".to_string());
+        let storage = Storage::new(folder, "file.txt".to_string());
+        let long_line = format!("{}\n", "a".repeat(10_000));
+
+        storage.write(format!("first\n{long_line}last\n")).unwrap();
+        storage.replace_last_line("changed\n".to_string()).unwrap();
+        assert_eq!(
+            storage.read().unwrap(),
+            format!("first\n{long_line}changed\n")
+        );
+
+        storage.replace_last_line("again\n".to_string()).unwrap();
+        storage.replace_last_line("only\n".to_string()).unwrap();
+        assert_eq!(storage.read().unwrap(), format!("first\n{long_line}only\n"));
+
+        storage.write(long_line).unwrap();
+        storage.replace_last_line("short\n".to_string()).unwrap();
+        assert_eq!(storage.read().unwrap(), "short\n");
+
+        let _ = remove_dir_all(format!(
+            "{}/{}",
+            get_home_path_with(home_dir),
+            "replace_folder"
+        ));
+    }
+
+    #[test]
+    fn test_storage_quarantine() {
+        let folder = Some("quarantine_folder".to_string());
+        let storage = Storage::new(folder, "file.txt".to_string());
+
+        storage.write("{\"broken\"".to_string()).unwrap();
+        let quarantined = storage.quarantine().unwrap();
+
+        assert!(storage.read().is_err());
+        assert_eq!(fs::read_to_string(&quarantined).unwrap(), "{\"broken\"");
+        assert!(quarantined
+            .to_string_lossy()
+            .contains("quarantine_folder/file.txt.corrupt-"));
+
+        let _ = remove_dir_all(format!(
+            "{}/{}",
+            get_home_path_with(home_dir),
+            "quarantine_folder"
+        ));
+    }
+
+    #[test]
+    fn test_storage_remove() {
+        let folder = Some("remove_folder".to_string());
+        let storage = Storage::new(folder, "file.txt".to_string());
+
+        storage.write("Lorem ipsum".to_string()).unwrap();
+        assert!(storage.remove().is_ok());
+        assert!(storage.read().is_err());
+        assert!(storage.remove().is_err());
+
+        let _ = remove_dir_all(format!(
+            "{}/{}",
+            get_home_path_with(home_dir),
+            "remove_folder"
+        ));
+    }
+
     #[test]
     fn test_storage_write_folder_doesnt_exist() {
         let folder = Some("non_existant_folder".to_string());
@@ -1,104 +1,338 @@
-use chrono::Utc;
+use chrono::{DateTime, Local, Utc};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
 use indicatif::{ProgressBar, ProgressStyle};
+use std::io;
 use std::thread;
 use std::time::Duration;
 
+use crate::duration::{format_duration, seconds_before};
+use crate::hooks::{self, Context};
 use crate::json_serializable::JsonSerializable;
 use crate::notify;
-use crate::session::Session;
 use crate::session::SessionList;
-use crate::settings::Settings;
+use crate::session::{Session, SessionStatus, SESSIONS_FILE};
+use crate::settings::{Hooks, Settings};
 use crate::sound::*;
-use crate::storage::Storage;
+use crate::state::{CurrentState, Phase};
+use crate::stats;
+use crate::storage::{FileLock, Storage, DEFAULT_FOLDER};
 
-/// Represents the values of a timer, as well as the time worked in minutes.
+/// How often a running countdown is redrawn and checks for key presses.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How often the state of a running countdown is written to `current.json`.
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Represents the values of a timer, as well as the time worked in seconds.
 ///
 /// # Examples
 ///
 /// ```
-/// let timer = Timer { work_minutes: 25, break_minutes: 10, total_worked_minutes: 0 };
-/// println!("Current work/break distribution: {}/{}", timer.work_minutes, timer.break_minutes);
-/// println!("Time worked in total: {}", timer.total_worked_minutes);
-/// assert_eq!(timer.work_minutes, 25);
-/// assert_eq!(timer.break_minutes, 10);
-/// assert_eq!(timer.total_worked_minutes, 0);
+/// let timer = Timer::new(25 * 60, 10 * 60, 15 * 60, 4, 0);
+/// println!("Current work/break distribution: {}/{}", timer.work_seconds, timer.break_seconds);
+/// println!("Time worked in total: {}", timer.total_worked_seconds);
+/// assert_eq!(timer.work_seconds, 1500);
+/// assert_eq!(timer.break_seconds, 600);
+/// assert_eq!(timer.break_seconds_after(4), 900);
+/// assert_eq!(timer.total_worked_seconds, 0);
+/// assert_eq!(timer.paused_seconds, 0);
 /// ```
 #[derive(Debug)]
 pub struct Timer {
-    pub work_minutes: u64,
-    pub break_minutes: u64,
-    pub total_worked_minutes: u64,
+    pub work_seconds: u64,
+    pub break_seconds: u64,
+    pub long_break_seconds: u64,
+    // A long break is taken after every `long_break_interval` pomodoros.
+    pub long_break_interval: u64,
+    pub total_worked_seconds: u64,
+    // The amount of seconds the current cycle has been paused for.
+    pub paused_seconds: u64,
+    // The project and tags the sessions of this timer are recorded with.
+    pub project: Option<String>,
+    pub tags: Vec<String>,
+    // A note for the sessions, given up front instead of asking for one.
+    pub note: Option<String>,
+    // The time the work of the current cycle was recorded at, which finds
+    // its session once the break is over.
+    pub work_recorded_at: Option<DateTime<Utc>>,
 }
 
 impl Timer {
     /// Creates a new `Timer` instance with the specified work and break durations.
-    pub fn new(work_minutes: u64, break_minutes: u64, total_worked_minutes: u64) -> Timer {
+    pub fn new(
+        work_seconds: u64,
+        break_seconds: u64,
+        long_break_seconds: u64,
+        long_break_interval: u64,
+        total_worked_seconds: u64,
+    ) -> Timer {
         Timer {
-            work_minutes,
-            break_minutes,
-            total_worked_minutes,
+            work_seconds,
+            break_seconds,
+            long_break_seconds,
+            long_break_interval,
+            total_worked_seconds,
+            paused_seconds: 0,
+            project: None,
+            tags: Vec::new(),
+            note: None,
+            work_recorded_at: None,
         }
     }
 
-    /// Adds a number of minutes to the total number of minutes worked.
-    pub fn add_worked_minutes(&mut self, minutes: u64) {
-        self.total_worked_minutes += minutes;
+    /// Creates a new `Timer` instance with the durations from `settings`,
+    /// which are stored in minutes.
+    pub fn from_settings(settings: &Settings, total_worked_seconds: u64) -> Timer {
+        Timer::new(
+            settings.work_time * 60,
+            settings.break_time * 60,
+            settings.long_break_time * 60,
+            settings.long_break_interval,
+            total_worked_seconds,
+        )
+    }
+
+    /// Adds a number of seconds to the total number of seconds worked.
+    pub fn add_worked_seconds(&mut self, seconds: u64) {
+        self.total_worked_seconds += seconds;
+    }
+
+    /// Sets the work seconds to the amount specified in the argument.
+    pub fn set_work_seconds(&mut self, seconds: u64) {
+        self.work_seconds = seconds;
     }
 
-    /// Sets the work minutes to the amount specified in the argument.
-    pub fn set_work_minutes(&mut self, minutes: u64) {
-        self.work_minutes = minutes;
+    /// Sets the break seconds to the amount specified in the argument.
+    pub fn set_break_seconds(&mut self, seconds: u64) {
+        self.break_seconds = seconds;
     }
 
-    /// Sets the break minutes to the amount specified in the argument.
-    pub fn set_break_minutes(&mut self, minutes: u64) {
-        self.break_minutes = minutes;
+    /// Sets the project and the tags the sessions are recorded with.
+    pub fn set_labels(&mut self, project: Option<String>, tags: Vec<String>) {
+        self.project = project;
+        self.tags = tags;
+    }
+
+    /// Starts a session of this timer, which is labelled with its project
+    /// and tags.
+    pub fn new_session(&self, timestamp: DateTime<Utc>, work_seconds: u64) -> Session {
+        Session::new(Some(timestamp), work_seconds, 0)
+            .with_labels(self.project.clone(), self.tags.clone())
+    }
+
+    /// Sets the length of the long breaks, and how many pomodoros there are
+    /// between them.
+    pub fn set_long_break(&mut self, seconds: u64, interval: u64) {
+        self.long_break_seconds = seconds;
+        self.long_break_interval = interval;
+    }
+
+    /// Checks whether the break after the `completed`th pomodoro should be a
+    /// long break.
+    pub fn is_long_break(&self, completed: u64) -> bool {
+        self.long_break_interval > 0
+            && completed > 0
+            && completed.is_multiple_of(self.long_break_interval)
+    }
+
+    /// Gets the length of the break after the `completed`th pomodoro.
+    pub fn break_seconds_after(&self, completed: u64) -> u64 {
+        if self.is_long_break(completed) {
+            self.long_break_seconds
+        } else {
+            self.break_seconds
+        }
+    }
+
+    /// Counts how many full work/break cycles fit into `available` time,
+    /// taking long breaks into account.
+    ///
+    /// ## Arguments
+    /// * available: The time until the block of cycles has to end.
+    /// * completed: The amount of pomodoros already finished today.
+    pub fn cycles_fitting(&self, available: Duration, completed: u64) -> u64 {
+        let mut used = Duration::ZERO;
+        let mut cycles = 0;
+
+        loop {
+            let break_seconds = self.break_seconds_after(completed + cycles + 1);
+            let cycle = Duration::from_secs(self.work_seconds + break_seconds);
+
+            if cycle.is_zero() || used + cycle > available {
+                return cycles;
+            }
+
+            used += cycle;
+            cycles += 1;
+        }
+    }
+
+    /// Adds a duration to the time the current cycle has been paused for.
+    pub fn add_paused(&mut self, paused: Duration) {
+        self.paused_seconds += paused.as_secs();
     }
 }
 
-pub fn pomodoro_work_timer(timer: &mut Timer, settings: &Settings) {
-    // convert the input time to seconds
-    let time_to_sec = &timer.work_minutes * 60;
+/// Checks whether a key event should pause or resume the running timer.
+fn is_pause_key(key: &KeyEvent) -> bool {
+    key.kind == KeyEventKind::Press && matches!(key.code, KeyCode::Char('p') | KeyCode::Char(' '))
+}
 
-    let bar = ProgressBar::new(time_to_sec);
-    bar.set_style(
-        ProgressStyle::with_template(
-            "{spinner:.cyan} 🍅 [Time Remaining {bar:.40.cyan/gray}] {msg}",
-        )
-        .unwrap()
-        .progress_chars("█▓▒░"),
-    );
+/// Checks whether a key event is Ctrl-C. Raw mode swallows the interrupt
+/// signal, so it has to be handled by hand while a timer is running.
+fn is_interrupt_key(key: &KeyEvent) -> bool {
+    key.kind == KeyEventKind::Press
+        && key.code == KeyCode::Char('c')
+        && key.modifiers.contains(KeyModifiers::CONTROL)
+}
+
+/// Exits with the status Ctrl-C would have caused outside of raw mode,
+/// once the hooks which are still running, such as `abort`, are done.
+fn exit_interrupted() -> ! {
+    hooks::wait_for_pending();
+    std::process::exit(130);
+}
+
+/// How a countdown came to an end.
+#[derive(Debug, PartialEq)]
+enum CountdownEnd {
+    Finished,
+    // The user pressed Ctrl-C.
+    Interrupted,
+}
+
+/// A countdown which is measured against the wall clock, rather than by
+/// counting how many times the timer has slept.
+///
+/// The remaining time is always computed from the moment the countdown was
+/// started, so the timer does not drift when redrawing takes longer than
+/// expected. As the wall clock keeps running while the system is suspended,
+/// a laptop which is woken up after the deadline finishes the countdown
+/// right away, instead of continuing where it was put to sleep.
+///
+/// # Examples
+///
+/// ```
+/// let now = Utc::now();
+/// let mut countdown = Countdown::new(Duration::from_secs(60), now);
+/// countdown.pause(now + TimeDelta::seconds(10));
+/// assert_eq!(countdown.remaining(now + TimeDelta::seconds(30)), Duration::from_secs(50));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Countdown {
+    duration: Duration,
+    started_at: DateTime<Utc>,
+    // Set while the countdown is paused.
+    paused_at: Option<DateTime<Utc>>,
+    // The total time of all pauses which have been resumed.
+    paused: Duration,
+}
 
-    for elapsed in 0..time_to_sec {
-        let remaining = time_to_sec - elapsed;
+impl Countdown {
+    /// Creates a new `Countdown` lasting `duration`, started at `now`.
+    pub fn new(duration: Duration, now: DateTime<Utc>) -> Countdown {
+        Countdown {
+            duration,
+            started_at: now,
+            paused_at: None,
+            paused: Duration::ZERO,
+        }
+    }
 
-        let min = remaining / 60;
-        let sec = remaining % 60;
+    /// The total length of the countdown, excluding pauses.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
 
-        let min_formatted = format!("{:02}", min);
-        let sec_formatted = format!("{:02}", sec);
+    /// Gets the time which has been spent paused up until `now`, including
+    /// the current pause, if any.
+    pub fn paused(&self, now: DateTime<Utc>) -> Duration {
+        match self.paused_at {
+            Some(paused_at) => self.paused + since(paused_at, now),
+            None => self.paused,
+        }
+    }
 
-        bar.set_message(format!("{min_formatted}:{sec_formatted}"));
-        thread::sleep(Duration::from_secs(1));
-        bar.inc(1);
+    /// Gets the time which has been counted down at `now`. Never more than
+    /// the duration of the countdown.
+    pub fn elapsed(&self, now: DateTime<Utc>) -> Duration {
+        since(self.started_at, now)
+            .saturating_sub(self.paused(now))
+            .min(self.duration)
     }
 
-    println!("✅ Pomodoro Timer completed\n");
+    /// Gets the time which is left of the countdown at `now`.
+    pub fn remaining(&self, now: DateTime<Utc>) -> Duration {
+        self.duration - self.elapsed(now)
+    }
 
-    if settings.notification.enable {
-        notify::send_notification_work();
+    /// Checks whether the countdown has run out at `now`.
+    pub fn is_finished(&self, now: DateTime<Utc>) -> bool {
+        self.remaining(now).is_zero()
     }
 
-    play_sound(POMODORO_FINISH.to_vec(), 2);
+    /// Checks whether the countdown is currently paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
 
-    //increment the time worked
-    timer.add_worked_minutes(timer.work_minutes);
+    /// Pauses the countdown at `now`. Does nothing if already paused.
+    pub fn pause(&mut self, now: DateTime<Utc>) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(now);
+        }
+    }
+
+    /// Resumes the countdown at `now`. Does nothing if not paused.
+    pub fn resume(&mut self, now: DateTime<Utc>) {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.paused += since(paused_at, now);
+        }
+    }
+
+    /// Pauses the countdown if it is running, and resumes it otherwise.
+    pub fn toggle_pause(&mut self, now: DateTime<Utc>) {
+        if self.is_paused() {
+            self.resume(now);
+        } else {
+            self.pause(now);
+        }
+    }
 }
 
-pub fn pomodoro_break_timer(timer: &Timer, session_list: &mut SessionList, settings: &Settings) {
-    let break_time_sec = timer.break_minutes * 60;
+/// Gets the time between `from` and `to`. If the clock has been set
+/// backwards, so that `to` is before `from`, no time has passed.
+pub fn since(from: DateTime<Utc>, to: DateTime<Utc>) -> Duration {
+    (to - from).to_std().unwrap_or(Duration::ZERO)
+}
+
+/// Formats a duration as `mm:ss`, or `h:mm:ss` from an hour and up,
+/// rounding partial seconds up so the countdown shows `00:01` until it is
+/// actually done.
+pub fn format_remaining(remaining: Duration) -> String {
+    let secs = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, (secs % 3600) / 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
 
-    let bar = ProgressBar::new(break_time_sec);
+/// Counts down a `Countdown` on a progress bar, until it runs out. The
+/// countdown can be paused and resumed with `p` or space.
+///
+/// If raw mode could not be enabled, e.g. because the output is not a
+/// terminal, the countdown simply runs and cannot be paused.
+/// The state of the countdown is written to `current.json` when it starts,
+/// at intervals while it runs, and whenever it is paused or interrupted.
+/// The `pause` hook is run whenever the countdown is paused.
+fn run_countdown(
+    countdown: &mut Countdown,
+    state: &mut CurrentState,
+    hooks: &Hooks,
+) -> CountdownEnd {
+    let bar = ProgressBar::new(countdown.duration().as_secs());
     bar.set_style(
         ProgressStyle::with_template(
             "{spinner:.cyan} 🍅 [Time Remaining {bar:.40.cyan/gray}] {msg}",
@@ -107,34 +341,424 @@ pub fn pomodoro_break_timer(timer: &Timer, session_list: &mut SessionList, setti
         .progress_chars("█▓▒░"),
     );
 
-    for elapsed in 0..break_time_sec {
-        let remaining = break_time_sec - elapsed;
+    println!("Press p or space to pause/resume.");
+    let raw_mode = terminal::enable_raw_mode().is_ok();
+    let paused_before = state.paused_seconds;
+    save_state(state, countdown, paused_before, Utc::now());
 
-        let min = remaining / 60;
-        let sec = remaining % 60;
+    let end = loop {
+        let now = Utc::now();
+        if countdown.is_finished(now) {
+            break CountdownEnd::Finished;
+        }
 
-        let min_formatted = format!("{:02}", min);
-        let sec_formatted = format!("{:02}", sec);
+        if since(state.updated_at, now) >= SAVE_INTERVAL {
+            save_state(state, countdown, paused_before, now);
+        }
 
-        bar.set_message(format!("{min_formatted}:{sec_formatted}"));
-        thread::sleep(Duration::from_secs(1));
-        bar.inc(1);
+        let remaining = format_remaining(countdown.remaining(now));
+        bar.set_position(countdown.elapsed(now).as_secs());
+        if countdown.is_paused() {
+            bar.set_message(format!("{remaining} ⏸ Paused (press p or space to resume)"));
+        } else {
+            bar.set_message(remaining);
+        }
+
+        if !raw_mode {
+            thread::sleep(REDRAW_INTERVAL);
+            continue;
+        }
+
+        match event::poll(REDRAW_INTERVAL) {
+            Ok(true) => match event::read() {
+                Ok(Event::Key(key)) if is_pause_key(&key) => {
+                    countdown.toggle_pause(Utc::now());
+                    save_state(state, countdown, paused_before, Utc::now());
+                    if countdown.is_paused() {
+                        hooks::fire(hooks, hooks::Event::Pause, &Context::from_state(state));
+                    }
+                }
+                Ok(Event::Key(key)) if is_interrupt_key(&key) => break CountdownEnd::Interrupted,
+                _ => (),
+            },
+            Ok(false) => (),
+            Err(_) => thread::sleep(REDRAW_INTERVAL),
+        }
+    };
+
+    if raw_mode {
+        let _ = terminal::disable_raw_mode();
+    }
+    if end == CountdownEnd::Finished {
+        bar.set_position(countdown.duration().as_secs());
+        bar.finish_with_message(format_remaining(Duration::ZERO));
+    } else {
+        bar.abandon();
+        println!();
     }
+    save_state(state, countdown, paused_before, Utc::now());
 
-    let session = Session::new(
-        Some(Utc::now()),
-        timer.work_minutes as u32,
-        timer.break_minutes as u32,
+    end
+}
+
+/// Updates `state` from the countdown at `now`, and writes it to
+/// `current.json`.
+///
+/// ## Arguments
+/// * paused_before: The time the cycle was paused for before this countdown.
+pub fn save_state(
+    state: &mut CurrentState,
+    countdown: &Countdown,
+    paused_before: u64,
+    now: DateTime<Utc>,
+) {
+    state.elapsed_seconds = countdown.elapsed(now).as_secs();
+    state.paused = countdown.is_paused();
+    state.paused_seconds = paused_before + countdown.paused(now).as_secs();
+    state.updated_at = now;
+
+    // The state is only needed if Tomato is closed before the timer is done,
+    // so failing to write it should not stop the timer.
+    let _ = state.save();
+}
+
+/// Runs the countdown of a phase of the cycle. If the phase is resumed,
+/// the countdown continues from where it was left.
+///
+/// The hooks for the start and the end of the phase are run, or the `abort`
+/// hook if the countdown is interrupted.
+///
+/// ## Arguments
+/// * phase: The phase which is counted down.
+/// * duration_seconds: The length of the phase.
+/// * elapsed_seconds: How much of the phase was counted down before.
+///
+/// ## Returns
+/// How the countdown ended, and the countdown itself.
+fn run_phase(
+    timer: &Timer,
+    phase: Phase,
+    duration_seconds: u64,
+    elapsed_seconds: u64,
+    hooks: &Hooks,
+) -> (CountdownEnd, Countdown) {
+    let now = Utc::now();
+    let started_at = seconds_before(now, elapsed_seconds.min(duration_seconds)).unwrap_or(now);
+
+    let mut countdown = Countdown::new(Duration::from_secs(duration_seconds), started_at);
+    let mut state = CurrentState::new(
+        phase,
+        duration_seconds,
+        timer.work_seconds,
+        timer.paused_seconds,
     );
+    state.project = timer.project.clone();
+    state.tags = timer.tags.clone();
+    state.work_recorded_at = timer.work_recorded_at;
 
-    let storage = Storage::new(None, "sessions.json".to_string());
+    let (start_event, end_event) = match phase {
+        Phase::Work => (hooks::Event::WorkStart, hooks::Event::WorkEnd),
+        Phase::Break => (hooks::Event::BreakStart, hooks::Event::BreakEnd),
+    };
+    hooks::fire(hooks, start_event, &Context::from_state(&state));
 
-    session_list.append(session);
+    let end = run_countdown(&mut countdown, &mut state, hooks);
+    let end_event = match end {
+        CountdownEnd::Finished => end_event,
+        CountdownEnd::Interrupted => hooks::Event::Abort,
+    };
+    hooks::fire(hooks, end_event, &Context::from_state(&state));
+
+    (end, countdown)
+}
+
+/// The folder in the home folder where the sessions are stored.
+const SESSIONS_FOLDER: &str = DEFAULT_FOLDER;
 
-    match storage.write(session_list.to_json()) {
+/// Locks `sessions.jsonl` in `folder`, so that other instances of Tomato
+/// wait before changing it. If the file system cannot lock files, the
+/// sessions are changed without a lock.
+fn lock_sessions(folder: &str) -> Option<FileLock> {
+    sessions_storage(folder).lock().ok()
+}
+
+/// The storage of `sessions.jsonl` in `folder`.
+fn sessions_storage(folder: &str) -> Storage {
+    Storage::new(Some(folder.to_string()), SESSIONS_FILE.to_string())
+}
+
+/// Writes the whole session list to `sessions.jsonl`, e.g. after a session
+/// which was done earlier was changed.
+fn save_sessions(folder: &str, session_list: &SessionList) {
+    match sessions_storage(folder).write(session_list.to_jsonl()) {
         Ok(_) => (),
         Err(v) => panic!("There was an error while writing to file. {}", v),
     }
+}
+
+/// Changes the sessions, e.g. to edit or import some, and writes them to
+/// `sessions.jsonl`. The sessions are read again first, while the file is
+/// locked, so that sessions which other instances of Tomato recorded in the
+/// meantime are kept.
+///
+/// ## Arguments
+/// * change: Changes the sessions. It should not ask the user anything, as
+///   other instances of Tomato wait for it.
+///
+/// ## Returns
+/// What `change` returns.
+pub fn change_sessions<T>(
+    session_list: &mut SessionList,
+    change: impl FnOnce(&mut SessionList) -> T,
+) -> T {
+    let _lock = lock_sessions(SESSIONS_FOLDER);
+    *session_list = SessionList::read_sessions(SESSIONS_FOLDER.to_string());
+
+    let changed = change(session_list);
+    save_sessions(SESSIONS_FOLDER, session_list);
+    changed
+}
+
+/// Appends a session to the session list, and to the end of
+/// `sessions.jsonl`, without writing the sessions before it again. The
+/// session list is read again first, so that it also has the sessions which
+/// other instances of Tomato recorded since it was loaded.
+pub fn record_session(session_list: &mut SessionList, session: Session) {
+    append_session(SESSIONS_FOLDER, session_list, session);
+}
+
+/// Appends a session to `sessions.jsonl` in `folder`, as `record_session`.
+fn append_session(folder: &str, session_list: &mut SessionList, session: Session) {
+    let line = session.to_json() + "\n";
+
+    let _lock = lock_sessions(folder);
+    *session_list = SessionList::read_sessions(folder.to_string());
+    session_list.append(session);
+
+    if let Err(v) = sessions_storage(folder).append(line) {
+        panic!("There was an error while writing to file. {}", v);
+    }
+}
+
+/// Writes a session which was just changed to `sessions.jsonl` in `folder`.
+/// If it is the last session in the file, as it usually is, only its line
+/// is written. Otherwise, another instance of Tomato recorded a session
+/// after it, and the whole file is written. If another instance removed the
+/// session, the user is told that the change was not saved.
+fn save_session(folder: &str, session: &Session) {
+    let storage = sessions_storage(folder);
+
+    let _lock = lock_sessions(folder);
+    let mut recorded = SessionList::read_sessions(folder.to_string());
+    let Some(id) = recorded.id_of(session) else {
+        eprintln!(
+            "The session from {} is no longer recorded, so its changes were not saved.",
+            session
+                .timestamp
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M")
+        );
+        return;
+    };
+
+    let written = if recorded.entries().last().map(|(last, _)| last) == Some(id) {
+        storage.replace_last_line(session.to_json() + "\n")
+    } else {
+        if let Some(recorded) = recorded.get_mut(id) {
+            *recorded = session.clone();
+        }
+        storage.write(recorded.to_jsonl())
+    };
+
+    if let Err(v) = written {
+        panic!("There was an error while writing to file. {}", v);
+    }
+}
+
+/// Sets the note of the last session, which was just recorded.
+pub fn record_note(session_list: &mut SessionList, note: String) {
+    if let Some(session) = session_list.last_mut() {
+        session.note = Some(note);
+        save_session(SESSIONS_FOLDER, session);
+    }
+}
+
+/// Asks the user what they accomplished in the pomodoro which just ended.
+fn ask_for_note() -> String {
+    println!("What did you accomplish? (Press Enter to skip)");
+
+    let mut note = String::new();
+    match io::stdin().read_line(&mut note) {
+        Ok(_) => note.trim().to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+/// Records the break of the session of `timer`, which was recorded as soon
+/// as its work was done. The sessions are read again first, as another
+/// instance of Tomato may have recorded or removed sessions since.
+///
+/// ## Arguments
+/// * timer: The timer of the cycle, with the time its work was recorded at
+///   and the time the whole cycle was paused for.
+/// * break_seconds: The length of the break which was taken.
+/// * status: The status of the session after the break.
+///
+/// ## Returns
+/// The updated session, or `None` if the session of the work is not
+/// recorded (anymore).
+pub fn record_break(
+    session_list: &mut SessionList,
+    timer: &Timer,
+    break_seconds: u64,
+    status: SessionStatus,
+) -> Option<Session> {
+    save_break(SESSIONS_FOLDER, session_list, timer, break_seconds, status)
+}
+
+/// Records the break of the session of `timer` in `folder`, as
+/// `record_break`.
+fn save_break(
+    folder: &str,
+    session_list: &mut SessionList,
+    timer: &Timer,
+    break_seconds: u64,
+    status: SessionStatus,
+) -> Option<Session> {
+    let work = Session::new(Some(timer.work_recorded_at?), timer.work_seconds, 0);
+
+    *session_list = SessionList::read_sessions(folder.to_string());
+    let id = session_list.id_of(&work)?;
+    let session = session_list.get_mut(id)?;
+    session.break_seconds = break_seconds;
+    session.status = status;
+    session.paused_seconds = timer.paused_seconds;
+    let session = session.clone();
+
+    save_session(folder, &session);
+    Some(session)
+}
+
+pub fn pomodoro_work_timer(timer: &mut Timer, session_list: &mut SessionList, settings: &Settings) {
+    timer.paused_seconds = 0;
+    timer.work_recorded_at = None;
+    resume_work_timer(timer, 0, session_list, settings);
+}
+
+/// Continues a work timer, which has already counted down `elapsed_seconds`.
+///
+/// The work is recorded as a session as soon as it is done, so it is kept
+/// even if the break is skipped. If the timer is interrupted with Ctrl-C,
+/// the part of the work which was done is recorded as aborted, and Tomato
+/// exits. Once the work is done, the note of the timer is stored on the
+/// session, or the user is asked for one if `prompt_for_note` is set.
+pub fn resume_work_timer(
+    timer: &mut Timer,
+    elapsed_seconds: u64,
+    session_list: &mut SessionList,
+    settings: &Settings,
+) {
+    let done_today = session_list.pomodoros_on(Local::now().date_naive());
+    println!("{}", stats::goal_progress(done_today, settings.daily_goal));
+
+    let (end, countdown) = run_phase(
+        timer,
+        Phase::Work,
+        timer.work_seconds,
+        elapsed_seconds,
+        &settings.hooks,
+    );
+    let now = Utc::now();
+    timer.add_paused(countdown.paused(now));
+
+    if end == CountdownEnd::Interrupted {
+        let worked = countdown.elapsed(now).as_secs();
+        let mut session = timer.new_session(now, worked);
+        session.status = SessionStatus::Aborted;
+        session.paused_seconds = timer.paused_seconds;
+
+        record_session(session_list, session);
+        CurrentState::clear();
+
+        println!(
+            "⏹ Pomodoro aborted, recorded {} of work.",
+            format_duration(worked)
+        );
+        exit_interrupted();
+    }
+
+    // Until the break is over, the session counts as having skipped it.
+    let mut session = timer.new_session(now, timer.work_seconds);
+    session.status = SessionStatus::SkippedBreak;
+    session.paused_seconds = timer.paused_seconds;
+
+    record_session(session_list, session);
+    timer.work_recorded_at = Some(now);
+    CurrentState::clear();
+
+    println!("✅ Pomodoro Timer completed\n");
+
+    if settings.notification.enable {
+        notify::send_notification_work();
+    }
+
+    play_sound(POMODORO_FINISH.to_vec(), 2);
+
+    //increment the time worked
+    timer.add_worked_seconds(timer.work_seconds);
+
+    let note = match &timer.note {
+        Some(note) => note.clone(),
+        None if settings.prompt_for_note => ask_for_note(),
+        None => String::new(),
+    };
+    if !note.is_empty() {
+        record_note(session_list, note);
+    }
+}
+
+pub fn pomodoro_break_timer(
+    timer: &mut Timer,
+    break_seconds: u64,
+    session_list: &mut SessionList,
+    settings: &Settings,
+) -> Session {
+    resume_break_timer(timer, break_seconds, 0, session_list, settings)
+}
+
+/// Continues a break timer, which has already counted down
+/// `elapsed_seconds`, and records the break on the session of its work when
+/// it is done. If the timer is interrupted with Ctrl-C, the part of the break
+/// which was taken is recorded, and Tomato exits.
+pub fn resume_break_timer(
+    timer: &mut Timer,
+    break_seconds: u64,
+    elapsed_seconds: u64,
+    session_list: &mut SessionList,
+    settings: &Settings,
+) -> Session {
+    let (end, countdown) = run_phase(
+        timer,
+        Phase::Break,
+        break_seconds,
+        elapsed_seconds,
+        &settings.hooks,
+    );
+    let now = Utc::now();
+    timer.add_paused(countdown.paused(now));
+
+    if end == CountdownEnd::Interrupted {
+        let taken = countdown.elapsed(now).as_secs();
+        record_break(session_list, timer, taken, SessionStatus::SkippedBreak);
+        CurrentState::clear();
+
+        println!("⏹ Break aborted after {}.", format_duration(taken));
+        exit_interrupted();
+    }
+
+    let session = record_break(session_list, timer, break_seconds, SessionStatus::Completed)
+        .unwrap_or_else(|| Session::new(Some(now), timer.work_seconds, break_seconds));
+    CurrentState::clear();
 
     println!("✅ Break is completed\n");
 
@@ -143,4 +767,240 @@ pub fn pomodoro_break_timer(timer: &Timer, session_list: &mut SessionList, setti
     }
 
     play_sound(BREAK_FINISH.to_vec(), 2);
+
+    session
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone};
+
+    use super::*;
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).single().unwrap()
+    }
+
+    #[test]
+    fn test_countdown_remaining_is_computed_from_the_clock() {
+        let countdown = Countdown::new(Duration::from_secs(25 * 60), start());
+
+        assert_eq!(countdown.remaining(start()), Duration::from_secs(25 * 60));
+        assert_eq!(
+            countdown.remaining(start() + TimeDelta::seconds(90)),
+            Duration::from_secs(23 * 60 + 30)
+        );
+        assert!(!countdown.is_finished(start() + TimeDelta::seconds(90)));
+    }
+
+    #[test]
+    fn test_countdown_finishes_after_a_long_suspend() {
+        let countdown = Countdown::new(Duration::from_secs(25 * 60), start());
+        let after_suspend = start() + TimeDelta::hours(3);
+
+        assert!(countdown.is_finished(after_suspend));
+        assert_eq!(countdown.elapsed(after_suspend), countdown.duration());
+    }
+
+    #[test]
+    fn test_countdown_clock_set_backwards_does_not_underflow() {
+        let countdown = Countdown::new(Duration::from_secs(60), start());
+
+        assert_eq!(
+            countdown.remaining(start() - TimeDelta::minutes(5)),
+            Duration::from_secs(60)
+        );
+    }
+
+    #[test]
+    fn test_countdown_pause_stops_the_clock() {
+        let mut countdown = Countdown::new(Duration::from_secs(60), start());
+        countdown.pause(start() + TimeDelta::seconds(10));
+
+        assert!(countdown.is_paused());
+        assert_eq!(
+            countdown.remaining(start() + TimeDelta::seconds(40)),
+            Duration::from_secs(50)
+        );
+        assert_eq!(
+            countdown.paused(start() + TimeDelta::seconds(40)),
+            Duration::from_secs(30)
+        );
+
+        countdown.resume(start() + TimeDelta::seconds(40));
+
+        assert!(!countdown.is_paused());
+        assert_eq!(
+            countdown.remaining(start() + TimeDelta::seconds(50)),
+            Duration::from_secs(40)
+        );
+        assert!(countdown.is_finished(start() + TimeDelta::seconds(90)));
+    }
+
+    #[test]
+    fn test_countdown_toggle_pause() {
+        let mut countdown = Countdown::new(Duration::from_secs(60), start());
+        countdown.toggle_pause(start());
+        countdown.toggle_pause(start() + TimeDelta::seconds(5));
+
+        assert!(!countdown.is_paused());
+        assert_eq!(
+            countdown.paused(start() + TimeDelta::seconds(5)),
+            Duration::from_secs(5)
+        );
+    }
+
+    #[test]
+    fn test_timer_long_break_every_interval() {
+        let timer = Timer::new(25 * 60, 5 * 60, 15 * 60, 4, 0);
+
+        assert_eq!(timer.break_seconds_after(1), 5 * 60);
+        assert_eq!(timer.break_seconds_after(3), 5 * 60);
+        assert_eq!(timer.break_seconds_after(4), 15 * 60);
+        assert_eq!(timer.break_seconds_after(8), 15 * 60);
+        assert_eq!(timer.break_seconds_after(9), 5 * 60);
+    }
+
+    #[test]
+    fn test_timer_long_break_disabled() {
+        let timer = Timer::new(25 * 60, 5 * 60, 15 * 60, 0, 0);
+
+        assert!(!timer.is_long_break(4));
+        assert_eq!(timer.break_seconds_after(4), 5 * 60);
+    }
+
+    #[test]
+    fn test_timer_cycles_fitting() {
+        let timer = Timer::new(25 * 60, 5 * 60, 15 * 60, 4, 0);
+        let hours = |h: u64| Duration::from_secs(h * 60 * 60);
+
+        assert_eq!(timer.cycles_fitting(Duration::from_secs(29 * 60), 0), 0);
+        assert_eq!(timer.cycles_fitting(Duration::from_secs(30 * 60), 0), 1);
+        // 3 short cycles and a long one take 130 minutes.
+        assert_eq!(timer.cycles_fitting(hours(2), 0), 3);
+        assert_eq!(timer.cycles_fitting(Duration::from_secs(130 * 60), 0), 4);
+        // After 3 pomodoros today, the first cycle ends with a long break.
+        assert_eq!(timer.cycles_fitting(Duration::from_secs(30 * 60), 3), 0);
+        assert_eq!(timer.cycles_fitting(Duration::from_secs(40 * 60), 3), 1);
+    }
+
+    #[test]
+    fn test_timer_cycles_fitting_seconds() {
+        let timer = Timer::new(90, 30, 30, 4, 0);
+
+        assert_eq!(timer.cycles_fitting(Duration::from_secs(5 * 60), 0), 2);
+    }
+
+    #[test]
+    fn test_timer_from_settings_converts_minutes() {
+        let settings = Settings::new(50, 10, crate::settings::Notifications::default());
+        let timer = Timer::from_settings(&settings, 60);
+
+        assert_eq!(timer.work_seconds, 50 * 60);
+        assert_eq!(timer.break_seconds, 10 * 60);
+        assert_eq!(timer.long_break_seconds, settings.long_break_time * 60);
+        assert_eq!(timer.total_worked_seconds, 60);
+    }
+
+    #[test]
+    fn test_timer_cycles_fitting_zero_length() {
+        let timer = Timer::new(0, 0, 0, 4, 0);
+
+        assert_eq!(timer.cycles_fitting(Duration::from_secs(60), 0), 0);
+    }
+
+    #[test]
+    fn test_format_remaining_rounds_up() {
+        assert_eq!(format_remaining(Duration::from_secs(25 * 60)), "25:00");
+        assert_eq!(format_remaining(Duration::from_millis(500)), "00:01");
+        assert_eq!(format_remaining(Duration::ZERO), "00:00");
+        assert_eq!(format_remaining(Duration::from_secs(90 * 60)), "1:30:00");
+    }
+
+    #[test]
+    fn test_break_and_note_are_saved_after_the_work() {
+        let folder = ".tomato_test_record";
+        let mut sessions = SessionList::new(None);
+        let session = Session {
+            status: SessionStatus::SkippedBreak,
+            ..Session::new(Some(Utc::now()), 25 * 60, 0)
+        };
+        append_session(folder, &mut sessions, session);
+
+        let last = sessions.last_mut().unwrap();
+        last.break_seconds = 5 * 60;
+        last.status = SessionStatus::Completed;
+        last.note = Some("Fixed the parser".to_string());
+        save_session(folder, last);
+
+        let recorded = SessionList::read_sessions(folder.to_string());
+        let session = recorded.iter().next().unwrap();
+        assert_eq!(recorded.iter().count(), 1);
+        assert_eq!(session.break_seconds, 5 * 60);
+        assert_eq!(session.status, SessionStatus::Completed);
+        assert_eq!(session.note, Some("Fixed the parser".to_string()));
+
+        let _ = std::fs::remove_dir_all(sessions_storage(folder).path().parent().unwrap());
+    }
+
+    #[test]
+    fn test_break_is_saved_on_the_session_of_its_work() {
+        let folder = ".tomato_test_break";
+        let mut sessions = SessionList::new(None);
+        let mut timer = Timer::new(25 * 60, 5 * 60, 15 * 60, 0, 0);
+        let work_done_at = Utc::now() - TimeDelta::minutes(5);
+        timer.work_recorded_at = Some(work_done_at);
+        timer.paused_seconds = 30;
+
+        let work = timer.new_session(work_done_at, timer.work_seconds);
+        append_session(folder, &mut sessions, work);
+        // Another instance records a session during the break.
+        let other = Session::new(Some(Utc::now()), 10 * 60, 0);
+        append_session(folder, &mut SessionList::new(None), other);
+
+        let session = save_break(
+            folder,
+            &mut sessions,
+            &timer,
+            5 * 60,
+            SessionStatus::Completed,
+        )
+        .unwrap();
+        assert_eq!(session.timestamp.timestamp(), work_done_at.timestamp());
+
+        let recorded = SessionList::read_sessions(folder.to_string());
+        let (first, second) = (recorded.get(1).unwrap(), recorded.get(2).unwrap());
+        assert_eq!(first.break_seconds, 5 * 60);
+        assert_eq!(first.status, SessionStatus::Completed);
+        assert_eq!(first.paused_seconds, 30);
+        assert_eq!(second.break_seconds, 0);
+        assert_eq!(second.work_seconds, 10 * 60);
+
+        let _ = std::fs::remove_dir_all(sessions_storage(folder).path().parent().unwrap());
+    }
+
+    #[test]
+    fn test_break_is_not_saved_when_its_work_is_gone() {
+        let folder = ".tomato_test_break_gone";
+        let mut sessions = SessionList::new(None);
+        let mut timer = Timer::new(25 * 60, 5 * 60, 15 * 60, 0, 0);
+        timer.work_recorded_at = Some(Utc::now());
+
+        let other = Session::new(Some(Utc::now() - TimeDelta::hours(1)), 25 * 60, 0);
+        append_session(folder, &mut sessions, other);
+
+        let saved = save_break(
+            folder,
+            &mut sessions,
+            &timer,
+            5 * 60,
+            SessionStatus::Completed,
+        );
+        assert!(saved.is_none());
+
+        let recorded = SessionList::read_sessions(folder.to_string());
+        assert_eq!(recorded.get(1).unwrap().break_seconds, 0);
+
+        let _ = std::fs::remove_dir_all(sessions_storage(folder).path().parent().unwrap());
+    }
 }
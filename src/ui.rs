@@ -1,29 +1,26 @@
-use crate::migration::{is_correct_version, migrate_settings};
-use crate::settings::SETTINGS_VERSION;
 use crate::{
+    duration::format_duration,
     json_serializable::JsonSerializable,
     menu,
-    session::SessionList,
-    settings::Settings,
+    render::{self, Output, Render},
+    session::{parse_tags, Session, SessionList, SessionStatus},
+    settings::{Settings, MAX_DAILY_GOAL},
+    state::{CurrentState, Phase},
+    stats::Summary,
     storage::Storage,
     timers::{self, Timer},
 };
+use chrono::{DateTime, Local};
 use crossterm::{cursor, execute, terminal};
+use serde_json::{json, Value};
 use std::io;
 
-pub fn ui_loop(sessions: &mut SessionList, settings: &mut Settings) {
-    let settings_json = Storage::new(Some(".tomato".to_string()), "settings.json".to_string());
-    let settings_json = settings_json.read().unwrap();
-
-    let checked_settings: &mut Settings =
-        if !is_correct_version(settings_json.as_str(), SETTINGS_VERSION) {
-            &mut migrate_settings(settings_json.as_str())
-        } else {
-            settings
-        };
+/// The amount of sessions shown by the history in the menu.
+const HISTORY_LIMIT: usize = 20;
 
+pub fn ui_loop(sessions: &mut SessionList, settings: &mut Settings) {
     loop {
-        if ui(sessions, checked_settings) == 9 {
+        if ui(sessions, settings) == 9 {
             break;
         }
     }
@@ -71,14 +68,40 @@ pub fn user_input(timer: &mut Timer, settings: &mut Settings) {
 
     let input_break: u64 = get_number_from_input();
 
-    timer.set_work_minutes(input_work);
-    timer.set_break_minutes(input_break);
+    // time input for long break time
+    println!("How long should the long breaks be?");
+    println!("Please input in minutes: ");
+
+    let input_long_break: u64 = get_number_from_input();
+
+    println!("How many pomodoros should there be between long breaks?");
+    println!("Please input a number (0 disables long breaks): ");
+
+    let input_interval: u64 = get_number_from_input();
+
+    println!("How many pomodoros do you want to do each day?");
+    println!("Please input a number up to {MAX_DAILY_GOAL} (0 disables the daily goal): ");
+
+    let input_goal: u64 = loop {
+        let goal = get_number_from_input();
+        if goal <= MAX_DAILY_GOAL {
+            break goal;
+        }
+        println!("That is more than a pomodoro every half hour. Please input a number up to {MAX_DAILY_GOAL}.");
+    };
+
+    timer.set_work_seconds(input_work * 60);
+    timer.set_break_seconds(input_break * 60);
+    timer.set_long_break(input_long_break * 60, input_interval);
 
     let file_name = String::from("settings.json");
     let settings_storage = Storage::new(None, file_name);
 
     settings.work_time = input_work;
     settings.break_time = input_break;
+    settings.long_break_time = input_long_break;
+    settings.long_break_interval = input_interval;
+    settings.daily_goal = input_goal;
 
     settings_storage
         .write(settings.to_json())
@@ -86,12 +109,15 @@ pub fn user_input(timer: &mut Timer, settings: &mut Settings) {
 }
 
 fn ui(session_list: &mut SessionList, settings: &mut Settings) -> u64 {
-    let total_minutes = session_list.total_work_minutes();
+    let total_seconds = session_list.total_work_seconds();
 
-    let mut timer = Timer::new(settings.work_time, settings.break_time, total_minutes);
+    let mut timer = Timer::from_settings(settings, total_seconds);
 
     loop {
-        menu::print_menu(settings);
+        menu::print_menu(
+            settings,
+            session_list.pomodoros_on(Local::now().date_naive()),
+        );
 
         // read user input
         let mut input = String::new();
@@ -115,11 +141,12 @@ fn ui(session_list: &mut SessionList, settings: &mut Settings) -> u64 {
                 get_input_before_going_back_to_menu();
             }
             2 => {
+                ask_for_labels(&mut timer);
                 start_cycle(&mut timer, session_list, settings);
                 get_input_before_going_back_to_menu();
             }
             3 => {
-                stats(&mut timer);
+                stats(session_list, settings);
                 get_input_before_going_back_to_menu();
             }
             4 => {
@@ -159,6 +186,33 @@ fn ui(session_list: &mut SessionList, settings: &mut Settings) -> u64 {
                     }
                 }
             }
+            6 if CurrentState::unfinished().is_some() => {
+                resume(session_list, settings);
+                get_input_before_going_back_to_menu();
+            }
+            7 => {
+                settings.prompt_for_note = !settings.prompt_for_note;
+
+                let settings_storage = Storage::new(None, String::from("settings.json"));
+                settings_storage
+                    .write(settings.to_json())
+                    .expect("Something went wrong while trying to write to settings.json");
+
+                if settings.prompt_for_note {
+                    println!("You will be asked for a note after each pomodoro.");
+                } else {
+                    println!("You will no longer be asked for notes.");
+                }
+            }
+            8 => {
+                println!("Search for (Press Enter to show all):");
+                let query = user_text_input();
+                render::print(
+                    &History::new(session_list, &query, Some(HISTORY_LIMIT)),
+                    Output::Table,
+                );
+                get_input_before_going_back_to_menu();
+            }
             9 => {
                 println!("Exiting...");
                 return 9;
@@ -168,7 +222,23 @@ fn ui(session_list: &mut SessionList, settings: &mut Settings) -> u64 {
     }
 }
 
-pub fn start_cycle(timer: &mut Timer, sessions: &mut SessionList, settings: &mut Settings) {
+/// Asks which project the pomodoro is for, and which tags it should have.
+/// Both can be left empty.
+fn ask_for_labels(timer: &mut Timer) {
+    println!("Which project is this for? (Press Enter for none)");
+    let project = user_text_input();
+
+    println!("Any tags, separated by commas? (Press Enter for none)");
+    let tags = parse_tags(&user_text_input());
+
+    timer.set_labels((!project.is_empty()).then_some(project), tags);
+}
+
+pub fn start_cycle(
+    timer: &mut Timer,
+    sessions: &mut SessionList,
+    settings: &mut Settings,
+) -> Session {
     execute!(
         std::io::stdout(),
         terminal::Clear(terminal::ClearType::All),
@@ -177,30 +247,267 @@ pub fn start_cycle(timer: &mut Timer, sessions: &mut SessionList, settings: &mut
     .unwrap();
 
     println!("\nStarting Pomodoro timer...");
-    timers::pomodoro_work_timer(timer, settings);
-    println!("...Press Enter to start the break...");
-    let mut dummy = String::new();
-    io::stdin().read_line(&mut dummy).unwrap();
-    timers::pomodoro_break_timer(timer, sessions, settings);
+    timers::pomodoro_work_timer(timer, sessions, settings);
+
+    take_break(timer, sessions, settings)
 }
 
-pub fn stats(timer: &mut Timer) {
-    let minutes = timer.total_worked_minutes;
+/// Waits for the user to start or skip the break after a pomodoro, picking
+/// a long break if it is time for one.
+///
+/// ## Returns
+/// The session of the pomodoro, as recorded after the break.
+fn take_break(timer: &mut Timer, sessions: &mut SessionList, settings: &Settings) -> Session {
+    // The pomodoro which was just finished has already been recorded.
+    let completed = sessions.pomodoros_on(Local::now().date_naive());
+    let break_seconds = timer.break_seconds_after(completed);
+
+    if timer.is_long_break(completed) {
+        println!("That was pomodoro number {completed} today. Time for a long break!");
+    }
+    println!("...Press Enter to start the break, or type s and press Enter to skip it...");
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).unwrap();
+
+    if input.trim().eq_ignore_ascii_case("s") {
+        println!("Break skipped.");
+        return sessions
+            .last_mut()
+            .map(|session| session.clone())
+            .expect("The pomodoro should have been recorded.");
+    }
+
+    timers::pomodoro_break_timer(timer, break_seconds, sessions, settings)
+}
+
+/// Offers to continue a timer which was interrupted, e.g. by closing the
+/// terminal, or to record the part of it which was finished.
+pub fn resume(sessions: &mut SessionList, settings: &mut Settings) {
+    let Some(state) = CurrentState::unfinished() else {
+        println!("There is no unfinished timer to resume.");
+        return;
+    };
 
     println!(
-        "You've worked for {} days, {} hours and {} minutes.",
-        (minutes / (60 * 24)), // Automatically rounds down
-        (minutes % (60 * 24)) / 60,
-        minutes % 60
+        "An unfinished {} timer was found, with {} of {} left, last seen {}.",
+        state.phase.name(),
+        format_duration(state.remaining_seconds()),
+        format_duration(state.duration_seconds),
+        state
+            .updated_at
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M")
     );
+    println!("1. Continue the timer");
+    println!("2. Record the finished part as a session");
+    println!("3. Discard it");
+
+    let mut timer = Timer::from_settings(settings, sessions.total_work_seconds());
+    timer.set_work_seconds(state.work_seconds);
+    timer.set_labels(state.project.clone(), state.tags.clone());
+    timer.paused_seconds = state.paused_seconds;
+    timer.work_recorded_at = state.work_recorded_at;
+
+    match get_number_from_input() {
+        1 => match state.phase {
+            Phase::Work => {
+                timers::resume_work_timer(&mut timer, state.elapsed_seconds, sessions, settings);
+                take_break(&mut timer, sessions, settings);
+            }
+            Phase::Break => {
+                timers::resume_break_timer(
+                    &mut timer,
+                    state.duration_seconds,
+                    state.elapsed_seconds,
+                    sessions,
+                    settings,
+                );
+            }
+        },
+        2 => {
+            match state.phase {
+                Phase::Work => {
+                    let mut session = timer.new_session(state.updated_at, state.elapsed_seconds);
+                    session.status = SessionStatus::Aborted;
+                    session.paused_seconds = state.paused_seconds;
 
-    if minutes == 0 {
-        println!("It's almost better than nothing!");
-    } else if minutes <= 25 {
-        println!("It's better than nothing!");
-    } else {
-        println!("Good job!");
+                    timers::record_session(sessions, session);
+                    println!(
+                        "Recorded {} of work.",
+                        format_duration(state.elapsed_seconds)
+                    );
+                }
+                Phase::Break => {
+                    // The work was recorded when it was done.
+                    let recorded = timers::record_break(
+                        sessions,
+                        &timer,
+                        state.elapsed_seconds,
+                        SessionStatus::SkippedBreak,
+                    );
+                    match recorded {
+                        Some(_) => println!(
+                            "Recorded {} of break.",
+                            format_duration(state.elapsed_seconds)
+                        ),
+                        None => println!(
+                            "The pomodoro before the break is no longer recorded, so the break was not recorded."
+                        ),
+                    }
+                }
+            }
+            CurrentState::clear();
+        }
+        3 => {
+            CurrentState::clear();
+            println!("The unfinished timer was discarded.");
+        }
+        _ => println!("Invalid option. The unfinished timer was kept."),
+    }
+}
+
+/// Runs work/break cycles back to back, and prints a summary of the block
+/// when it ends.
+///
+/// ## Arguments
+/// * cycles: The amount of cycles to run.
+/// * until: A deadline. Only cycles which can finish before it are run.
+pub fn run_block(
+    timer: &mut Timer,
+    sessions: &mut SessionList,
+    settings: &mut Settings,
+    cycles: Option<u64>,
+    until: Option<DateTime<Local>>,
+) {
+    let fitting = |timer: &Timer, sessions: &SessionList| match until {
+        Some(until) => {
+            let available = (until - Local::now()).to_std().unwrap_or_default();
+            timer.cycles_fitting(available, sessions.pomodoros_on(Local::now().date_naive()))
+        }
+        None => u64::MAX,
+    };
+
+    let planned = cycles.unwrap_or(u64::MAX).min(fitting(timer, sessions));
+    if planned == 0 {
+        if let Some(until) = until {
+            println!(
+                "There is not enough time left before {} for a single cycle.",
+                until.format("%H:%M")
+            );
+        }
+        return;
+    }
+
+    let mut block = Vec::new();
+    while (block.len() as u64) < planned {
+        // Waiting to start a break eats into the time left, so check again.
+        if fitting(timer, sessions) == 0 {
+            break;
+        }
+        block.push(start_cycle(timer, sessions, settings));
     }
+
+    print_block_summary(&block);
+}
+
+/// Prints how many cycles were run in a block, and how much time was spent
+/// working and on breaks.
+fn print_block_summary(block: &[Session]) {
+    let work: u64 = block.iter().map(|session| session.work_seconds).sum();
+    let breaks: u64 = block.iter().map(|session| session.break_seconds).sum();
+    let paused: u64 = block.iter().map(|session| session.paused_seconds).sum();
+
+    println!("🍅 Block finished: {} cycles", block.len());
+    println!("   Focus: {}", format_duration(work));
+    println!("   Breaks: {}", format_duration(breaks));
+    if paused > 0 {
+        println!("   Paused: {}", format_duration(paused));
+    }
+}
+
+/// The sessions shown by `tomato history`, newest first.
+pub struct History<'a> {
+    pub sessions: Vec<&'a Session>,
+}
+
+impl<'a> History<'a> {
+    /// Finds the sessions whose note, project or tags contain `query`.
+    ///
+    /// ## Arguments
+    /// * query: The text to search for. Empty to show all sessions.
+    /// * limit: The most sessions to show.
+    pub fn new(sessions: &'a SessionList, query: &str, limit: Option<usize>) -> History<'a> {
+        let mut found = sessions.search(query);
+        found.truncate(limit.unwrap_or(usize::MAX));
+
+        History { sessions: found }
+    }
+}
+
+impl Render for History<'_> {
+    fn table(&self) -> String {
+        if self.sessions.is_empty() {
+            return "No sessions found.".to_string();
+        }
+
+        let mut table = String::new();
+        for session in &self.sessions {
+            table.push_str(&format!(
+                "{}  {:>6}",
+                session
+                    .timestamp
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M"),
+                format_duration(session.work_seconds)
+            ));
+            if let Some(project) = &session.project {
+                table.push_str(&format!("  [{project}]"));
+            }
+            for tag in &session.tags {
+                table.push_str(&format!(" #{tag}"));
+            }
+            if let Some(note) = &session.note {
+                table.push_str(&format!("  {note}"));
+            }
+            table.push('\n');
+        }
+
+        table
+    }
+
+    /// A line for each session with its timestamp, work, break, project,
+    /// tags separated by commas, and note.
+    fn plain(&self) -> String {
+        self.sessions
+            .iter()
+            .map(|session| {
+                format!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\n",
+                    session.timestamp.to_rfc3339(),
+                    session.work_seconds,
+                    session.break_seconds,
+                    session.project.as_deref().unwrap_or(""),
+                    session.tags.join(","),
+                    session
+                        .note
+                        .as_deref()
+                        .unwrap_or("")
+                        .replace(['\t', '\n'], " ")
+                )
+            })
+            .collect()
+    }
+
+    /// The sessions as they are stored in `sessions.jsonl`.
+    fn json(&self) -> Value {
+        json!({ "sessions": self.sessions })
+    }
+}
+
+/// Prints the statistics of all sessions.
+pub fn stats(sessions: &SessionList, settings: &Settings) {
+    let summary = Summary::new(sessions, settings, Local::now().date_naive());
+    render::print(&summary, Output::Table);
 }
 
 // TODO: Get a new name for this function.